@@ -0,0 +1,143 @@
+//! Character sets used to print grid cells
+
+use std::char;
+use std::str::FromStr;
+
+use anyhow::Result;
+
+/// Braille dot values by row and column within a 2x4 cell
+const BRAILLE_LAYOUT: &[&[u32]] = &[&[0x01, 0x08], &[0x02, 0x10], &[0x04, 0x20], &[0x40, 0x80]];
+
+/// Upper and lower halves of a 1x2 cell
+const HALFBLOCK_LAYOUT: &[&[u32]] = &[&[0x1], &[0x2]];
+
+/// Quadrants of a 2x2 cell
+const QUADRANT_LAYOUT: &[&[u32]] = &[&[0x1, 0x2], &[0x4, 0x8]];
+
+/// Sextants of a 2x3 cell, numbered in the same order as Unicode block sextants
+const SEXTANT_LAYOUT: &[&[u32]] = &[&[0x01, 0x02], &[0x04, 0x08], &[0x10, 0x20]];
+
+const HALFBLOCK_CHARS: [char; 4] = [' ', '▀', '▄', '█'];
+
+const QUADRANT_CHARS: [char; 16] = [
+    ' ', '▘', '▝', '▀', '▖', '▌', '▞', '▛', '▗', '▚', '▐', '▜', '▄', '▙', '▟', '█',
+];
+
+/// Characters of increasing density for the number of filled sub-cells
+const ASCII_RAMP: [char; 9] = [' ', '.', ':', '-', '=', '+', '*', '#', '@'];
+
+/// Sextant values with existing block element characters instead of legacy computing sextants
+const SEXTANT_LEFT: u32 = 0x15;
+const SEXTANT_RIGHT: u32 = 0x2a;
+
+/// Characters used to print grid cells, each made up of sub-cells that can be filled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    /// Braille patterns with 2x4 dots
+    Braille,
+    /// Upper and lower half blocks
+    HalfBlock,
+    /// Quadrant blocks with 2x2 sub-cells
+    Quadrant,
+    /// Sextant blocks with 2x3 sub-cells
+    Sextant,
+    /// ASCII characters for terminals without Unicode support
+    Ascii,
+}
+
+impl FromStr for Charset {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Charset> {
+        match s.to_ascii_lowercase().as_ref() {
+            "braille" => Ok(Charset::Braille),
+            "halfblock" => Ok(Charset::HalfBlock),
+            "quadrant" => Ok(Charset::Quadrant),
+            "sextant" => Ok(Charset::Sextant),
+            "ascii" => Ok(Charset::Ascii),
+            c => Err(anyhow::anyhow!("Invalid charset supplied: {}", c)),
+        }
+    }
+}
+
+impl Charset {
+    /// Values of each sub-cell by row and column, combined to get the character for a cell
+    fn layout(&self) -> &'static [&'static [u32]] {
+        match self {
+            Charset::Braille | Charset::Ascii => BRAILLE_LAYOUT,
+            Charset::HalfBlock => HALFBLOCK_LAYOUT,
+            Charset::Quadrant => QUADRANT_LAYOUT,
+            Charset::Sextant => SEXTANT_LAYOUT,
+        }
+    }
+
+    /// Number of sub-cell rows in each cell
+    pub fn rows(&self) -> i32 {
+        self.layout().len() as i32
+    }
+
+    /// Number of sub-cell columns in each cell
+    pub fn cols(&self) -> i32 {
+        self.layout()[0].len() as i32
+    }
+
+    /// Value of a sub-cell to add to the cell value if it is filled
+    pub fn cell_value(&self, row: i32, col: i32) -> u32 {
+        self.layout()[row as usize][col as usize]
+    }
+
+    /// Value of a cell with all sub-cells filled
+    pub fn full_value(&self) -> u32 {
+        self.layout().iter().flat_map(|row| row.iter()).sum()
+    }
+
+    /// Get the character for the combined value of filled sub-cells
+    pub fn char(&self, value: u32) -> char {
+        match self {
+            Charset::Braille => char::from_u32(0x2800 + value).unwrap(),
+            Charset::HalfBlock => HALFBLOCK_CHARS[value as usize],
+            Charset::Quadrant => QUADRANT_CHARS[value as usize],
+            Charset::Sextant => match value {
+                0 => ' ',
+                SEXTANT_LEFT => '▌',
+                SEXTANT_RIGHT => '▐',
+                v if v == self.full_value() => '█',
+                // Legacy computing sextants skip the values with block element characters
+                v => {
+                    let skipped = u32::from(v > SEXTANT_LEFT) + u32::from(v > SEXTANT_RIGHT);
+                    char::from_u32(0x1fb00 + v - 1 - skipped).unwrap()
+                }
+            },
+            Charset::Ascii => ASCII_RAMP[value.count_ones() as usize],
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_layout() {
+        assert_eq!((Charset::Braille.cols(), Charset::Braille.rows()), (2, 4));
+        assert_eq!(
+            (Charset::HalfBlock.cols(), Charset::HalfBlock.rows()),
+            (1, 2)
+        );
+        assert_eq!((Charset::Sextant.cols(), Charset::Sextant.rows()), (2, 3));
+        assert_eq!(Charset::Braille.cell_value(3, 1), 0x80);
+    }
+
+    #[test]
+    fn test_char() {
+        assert_eq!(Charset::Braille.char(0x36), '⠶');
+        assert_eq!(Charset::Braille.char(Charset::Braille.full_value()), '⣿');
+        assert_eq!(Charset::HalfBlock.char(0x2), '▄');
+        assert_eq!(Charset::Quadrant.char(0x9), '▚');
+        assert_eq!(Charset::Sextant.char(0x01), '\u{1fb00}');
+        assert_eq!(Charset::Sextant.char(0x16), '\u{1fb14}');
+        assert_eq!(Charset::Sextant.char(0x3e), '\u{1fb3b}');
+        assert_eq!(Charset::Sextant.char(0x2a), '▐');
+        assert_eq!(Charset::Ascii.char(0x36), '=');
+    }
+}
@@ -0,0 +1,286 @@
+//! Color scales for coloring features by property or layer, with printed legends
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use anyhow::{Context, Result};
+use console::Style;
+use rstar::RTree;
+
+use crate::map_grid::{GridFeature, GridLayer};
+use crate::properties::PropValue;
+
+/// 256-color terminal codes for categorical values
+const CATEGORICAL_PALETTE: [u8; 10] = [39, 203, 77, 214, 170, 44, 227, 211, 137, 250];
+
+/// 256-color terminal codes for graduated numeric values, from low to high
+const GRADUATED_RAMP: [u8; 6] = [226, 220, 214, 208, 202, 196];
+
+/// Maps property values to terminal colors
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColorScale {
+    /// Distinct colors for each category, ordered by frequency
+    Categorical(Vec<String>),
+    /// Equal interval classes between the minimum and maximum value
+    Graduated {
+        /// Minimum value in the scale
+        min: f64,
+        /// Maximum value in the scale
+        max: f64,
+    },
+}
+
+impl ColorScale {
+    /// Create a graduated scale if all non-null values are numbers, otherwise categorical
+    pub fn new<'a, I>(values: I) -> ColorScale
+    where
+        I: Iterator<Item = &'a PropValue>,
+    {
+        let values = values
+            .filter(|v| **v != PropValue::Null)
+            .collect::<Vec<_>>();
+
+        if !values.is_empty() && values.iter().all(|v| v.as_f64().is_some()) {
+            let (min, max) = values
+                .iter()
+                .filter_map(|v| v.as_f64())
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+                    (min.min(v), max.max(v))
+                });
+            return ColorScale::Graduated { min, max };
+        }
+
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for value in values {
+            *counts.entry(value.to_string()).or_default() += 1;
+        }
+        let mut categories = counts.into_iter().collect::<Vec<_>>();
+        categories.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        ColorScale::Categorical(categories.into_iter().map(|(c, _)| c).collect())
+    }
+
+    /// Get the list of colors used by the scale, indexed by class
+    pub fn colors(&self) -> Vec<u8> {
+        match self {
+            ColorScale::Categorical(categories) => CATEGORICAL_PALETTE
+                .iter()
+                .take(categories.len().max(1))
+                .copied()
+                .collect(),
+            ColorScale::Graduated { .. } => GRADUATED_RAMP.to_vec(),
+        }
+    }
+
+    /// Get the color class of a value, or None if the value is null or not in the scale
+    pub fn class_index(&self, value: &PropValue) -> Option<usize> {
+        match (self, value) {
+            (_, PropValue::Null) => None,
+            (ColorScale::Categorical(categories), value) => {
+                let value = value.to_string();
+                categories
+                    .iter()
+                    .position(|c| *c == value)
+                    // Less frequent categories share the last color if there are too many
+                    .map(|idx| idx.min(CATEGORICAL_PALETTE.len() - 1))
+            }
+            (ColorScale::Graduated { min, max }, value) => {
+                let value = value.as_f64()?;
+                let classes = GRADUATED_RAMP.len();
+                if max <= min {
+                    return Some(0);
+                }
+                let idx = ((value - min) / (max - min) * classes as f64).floor() as usize;
+                Some(idx.min(classes - 1))
+            }
+        }
+    }
+
+    /// Labels describing the values included in each color class
+    pub fn labels(&self) -> Vec<String> {
+        match self {
+            ColorScale::Categorical(categories) => {
+                let palette_len = CATEGORICAL_PALETTE.len();
+                if categories.len() > palette_len {
+                    let mut labels = categories[..palette_len - 1].to_vec();
+                    labels.push(format!("Other ({})", categories.len() - palette_len + 1));
+                    labels
+                } else {
+                    categories.clone()
+                }
+            }
+            ColorScale::Graduated { min, max } => {
+                let classes = GRADUATED_RAMP.len();
+                let step = (max - min) / classes as f64;
+                (0..classes)
+                    .map(|idx| {
+                        let start = min + step * idx as f64;
+                        format!("{} - {}", format_number(start), format_number(start + step))
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Group feature geometries into a layer for each color class, with unmatched features last
+    pub fn layers(&self, property: &str, features: Vec<GridFeature<f64>>) -> Vec<GridLayer<f64>> {
+        let colors = self.colors();
+        let mut class_geoms = vec![vec![]; colors.len() + 1];
+        for feature in features {
+            let class = feature
+                .properties
+                .get(property)
+                .and_then(|v| self.class_index(v))
+                .unwrap_or(colors.len());
+            class_geoms[class].extend(feature.geoms);
+        }
+        class_geoms
+            .into_iter()
+            .enumerate()
+            .map(|(idx, geoms)| GridLayer {
+                color: colors.get(idx).copied(),
+                rtree: RTree::bulk_load(geoms),
+            })
+            .collect()
+    }
+
+    /// Print a legend with a colored block character and label for each class
+    pub fn print_legend(&self, property: &str, block: char) -> Result<()> {
+        let stdout = io::stdout();
+        let mut handle = io::BufWriter::new(stdout.lock());
+
+        writeln!(handle, "{}", property).context("Error printing legend")?;
+        for (color, label) in self.colors().into_iter().zip(self.labels()) {
+            writeln!(
+                handle,
+                "{} {}",
+                Style::new().color256(color).apply_to(block),
+                label
+            )
+            .context("Error printing legend")?;
+        }
+        Ok(())
+    }
+}
+
+/// Standard RGB values of the first 16 terminal colors
+const SYSTEM_COLORS: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (128, 0, 0),
+    (0, 128, 0),
+    (128, 128, 0),
+    (0, 0, 128),
+    (128, 0, 128),
+    (0, 128, 128),
+    (192, 192, 192),
+    (128, 128, 128),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (0, 0, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// Intensity levels of the 6x6x6 color cube in 256-color terminals
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Convert a 256-color terminal code to RGB
+pub fn color256_to_rgb(color: u8) -> (u8, u8, u8) {
+    match color {
+        0..=15 => SYSTEM_COLORS[usize::from(color)],
+        16..=231 => {
+            let idx = usize::from(color - 16);
+            (
+                CUBE_LEVELS[idx / 36],
+                CUBE_LEVELS[(idx / 6) % 6],
+                CUBE_LEVELS[idx % 6],
+            )
+        }
+        _ => {
+            let gray = 8 + (color - 232) * 10;
+            (gray, gray, gray)
+        }
+    }
+}
+
+/// Format an RGB color as a hex string like #ff0000
+pub fn rgb_to_hex((r, g, b): (u8, u8, u8)) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// Parse a hex color like #ff0000 or ff0000 to RGB
+pub fn parse_hex_color(color: &str) -> Result<(u8, u8, u8)> {
+    let hex = color.trim_start_matches('#');
+    let channel = |idx: usize| {
+        hex.get(idx..idx + 2)
+            .and_then(|c| u8::from_str_radix(c, 16).ok())
+            .with_context(|| format!("Could not parse hex color: {}", color))
+    };
+    if hex.len() != 6 {
+        return Err(anyhow::anyhow!("Hex color must have 6 digits: {}", color));
+    }
+    Ok((channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Format numbers for legend labels, dropping decimals for large values
+fn format_number(num: f64) -> String {
+    if num.abs() >= 100. {
+        format!("{:.0}", num)
+    } else {
+        format!("{:.2}", num)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_categorical_scale() {
+        let values = [
+            PropValue::String("IL".to_string()),
+            PropValue::String("WI".to_string()),
+            PropValue::String("IL".to_string()),
+            PropValue::Null,
+        ];
+        let scale = ColorScale::new(values.iter());
+        assert_eq!(
+            scale,
+            ColorScale::Categorical(vec!["IL".to_string(), "WI".to_string()])
+        );
+        assert_eq!(scale.class_index(&values[1]), Some(1));
+        assert_eq!(scale.class_index(&PropValue::Null), None);
+        assert_eq!(scale.colors().len(), 2);
+    }
+
+    #[test]
+    fn test_graduated_scale() {
+        let values = [
+            PropValue::Number(0.),
+            PropValue::Number(50.),
+            PropValue::Number(120.),
+        ];
+        let scale = ColorScale::new(values.iter());
+        assert_eq!(scale, ColorScale::Graduated { min: 0., max: 120. });
+        assert_eq!(scale.class_index(&values[0]), Some(0));
+        assert_eq!(scale.class_index(&values[1]), Some(2));
+        assert_eq!(scale.class_index(&values[2]), Some(5));
+        assert_eq!(scale.labels()[0], "0.00 - 20.00");
+    }
+
+    #[test]
+    fn test_color256_to_rgb() {
+        assert_eq!(color256_to_rgb(9), (255, 0, 0));
+        assert_eq!(rgb_to_hex(color256_to_rgb(39)), "#00afff");
+        assert_eq!(color256_to_rgb(250), (188, 188, 188));
+    }
+
+    #[test]
+    fn test_parse_hex_color() {
+        assert_eq!(parse_hex_color("#00afff").unwrap(), (0, 175, 255));
+        assert_eq!(parse_hex_color("FFFFFF").unwrap(), (255, 255, 255));
+        assert!(parse_hex_color("#fff").is_err());
+        assert!(parse_hex_color("#gggggg").is_err());
+    }
+}
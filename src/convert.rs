@@ -0,0 +1,249 @@
+//! Writing features to other formats
+
+use std::collections::BTreeSet;
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use geo::{Geometry, GeometryCollection, LineString};
+use geojson::JsonValue;
+use polyline::encode_coordinates;
+use wkt::ToWkt;
+
+use crate::properties::PropValue;
+use crate::reader::Feature;
+
+/// Number of decimal places used to encode polylines if not provided
+pub const DEFAULT_POLYLINE_PRECISION: u32 = 5;
+
+/// Name of the geometry column in CSV output, which can be read back with --geom-col
+const CSV_GEOM_COLUMN: &str = "wkt";
+
+/// Formats that features can be converted to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConvertFormat {
+    /// GeoJSON FeatureCollection
+    GeoJson,
+    /// Newline-delimited GeoJSON features
+    GeoJsonSeq,
+    /// Single WKT geometry, combining multiple features into a GeometryCollection
+    Wkt,
+    /// CSV with a WKT geometry column and a column for each property
+    Csv,
+    /// Encoded polyline of a single LineString
+    Polyline {
+        /// Number of decimal places to encode
+        precision: u32,
+    },
+}
+
+impl FromStr for ConvertFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<ConvertFormat> {
+        match s.to_ascii_lowercase().as_ref() {
+            "geojson" | "json" => Ok(ConvertFormat::GeoJson),
+            "geojsonseq" | "geojsonl" | "ndjson" => Ok(ConvertFormat::GeoJsonSeq),
+            "wkt" => Ok(ConvertFormat::Wkt),
+            "csv" => Ok(ConvertFormat::Csv),
+            "polyline" => Ok(ConvertFormat::Polyline {
+                precision: DEFAULT_POLYLINE_PRECISION,
+            }),
+            f => Err(anyhow::anyhow!("Invalid output format supplied: {}", f)),
+        }
+    }
+}
+
+impl ConvertFormat {
+    /// Get the format of an output file from its extension
+    pub fn from_path(file_path: &str) -> Result<ConvertFormat> {
+        Path::new(file_path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .with_context(|| format!("Output file {} has no extension", file_path))?
+            .parse()
+            .with_context(|| {
+                format!(
+                    "Could not detect the output format of {}, use a geojson, geojsonl, wkt, csv or polyline extension",
+                    file_path
+                )
+            })
+    }
+
+    /// Write features in this format
+    pub fn write<W: Write>(&self, features: &[Feature], mut writer: W) -> Result<()> {
+        match self {
+            ConvertFormat::GeoJson => {
+                // Members are written with the type first so that streaming readers know the
+                // object is a collection before reading its features
+                writeln!(writer, "{{\"type\":\"FeatureCollection\",\"features\":[")?;
+                for (idx, feature) in features.iter().enumerate() {
+                    let separator = if idx + 1 < features.len() { "," } else { "" };
+                    writeln!(writer, "{}{}", geojson_feature(feature), separator)?;
+                }
+                writeln!(writer, "]}}")?;
+            }
+            ConvertFormat::GeoJsonSeq => {
+                for feature in features {
+                    writeln!(writer, "{}", geojson_feature(feature))?;
+                }
+            }
+            ConvertFormat::Wkt => {
+                // Multiple features are combined so that the output is a single WKT geometry
+                let geom = match features {
+                    [feature] => feature.geometry.clone(),
+                    _ => Geometry::GeometryCollection(GeometryCollection(
+                        features.iter().map(|f| f.geometry.clone()).collect(),
+                    )),
+                };
+                writeln!(writer, "{}", geom.wkt_string())?;
+            }
+            ConvertFormat::Csv => write_csv(features, writer)?,
+            ConvertFormat::Polyline { precision } => {
+                let line = features_polyline(features)?;
+                let encoded = encode_coordinates(line, *precision)
+                    .map_err(|e| anyhow::anyhow!("There was an error encoding polyline: {}", e))?;
+                writeln!(writer, "{}", encoded)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Convert a feature to a GeoJSON feature with its properties
+fn geojson_feature(feature: &Feature) -> geojson::Feature {
+    geojson::Feature {
+        bbox: None,
+        geometry: Some(geojson::Geometry::new(geojson::Value::from(
+            &feature.geometry,
+        ))),
+        id: None,
+        properties: Some(
+            feature
+                .properties
+                .iter()
+                .map(|(k, v)| (k.clone(), JsonValue::from(v)))
+                .collect(),
+        ),
+        foreign_members: None,
+    }
+}
+
+/// Write features as CSV with a WKT geometry column and a column for each property
+fn write_csv<W: Write>(features: &[Feature], writer: W) -> Result<()> {
+    let columns: BTreeSet<&String> = features
+        .iter()
+        .flat_map(|f| f.properties.keys())
+        .filter(|k| k.as_str() != CSV_GEOM_COLUMN)
+        .collect();
+    let mut wtr = csv::Writer::from_writer(writer);
+    wtr.write_record(std::iter::once(CSV_GEOM_COLUMN).chain(columns.iter().map(|c| c.as_str())))
+        .context("There was an error writing CSV")?;
+    for feature in features {
+        let values = columns.iter().map(|c| match feature.properties.get(*c) {
+            Some(PropValue::Null) | None => String::new(),
+            Some(value) => value.to_string(),
+        });
+        wtr.write_record(std::iter::once(feature.geometry.wkt_string()).chain(values))
+            .context("There was an error writing CSV")?;
+    }
+    wtr.flush().context("There was an error writing CSV")?;
+    Ok(())
+}
+
+/// Get the line to encode as a polyline, which needs to be the only geometry
+fn features_polyline(features: &[Feature]) -> Result<LineString<f64>> {
+    let geoms: Vec<&Geometry<f64>> = features.iter().map(|f| &f.geometry).collect();
+    match geoms[..] {
+        [Geometry::LineString(line)] => Ok(line.clone()),
+        [Geometry::Line(line)] => Ok(LineString::from(vec![line.start, line.end])),
+        [Geometry::MultiLineString(lines)] if lines.0.len() == 1 => Ok(lines.0[0].clone()),
+        _ => Err(anyhow::anyhow!(
+            "Polyline output requires a single LineString, found {} features",
+            features.len()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::properties::Properties;
+    use geo::Point;
+    use geojson::FeatureIterator;
+
+    fn write_string(format: ConvertFormat, features: &[Feature]) -> String {
+        let mut output = vec![];
+        format.write(features, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    fn point_feature() -> Feature {
+        let mut properties = Properties::new();
+        properties.insert("id".to_string(), PropValue::Number(1.));
+        properties.insert("name".to_string(), PropValue::String("a, b".to_string()));
+        Feature::new(Geometry::Point(Point::new(1., 2.)), properties)
+    }
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(
+            ConvertFormat::from_path("out.GeoJSON").unwrap(),
+            ConvertFormat::GeoJson
+        );
+        assert_eq!(
+            ConvertFormat::from_path("out.ndjson").unwrap(),
+            ConvertFormat::GeoJsonSeq
+        );
+        assert!(ConvertFormat::from_path("out.shp").is_err());
+        assert!(ConvertFormat::from_path("out").is_err());
+    }
+
+    #[test]
+    fn test_write_geojson() {
+        let output = write_string(ConvertFormat::GeoJsonSeq, &[point_feature()]);
+        let feature: geojson::Feature = output.trim().parse().unwrap();
+        assert_eq!(feature.property("id"), Some(&JsonValue::from(1)));
+        let output = write_string(ConvertFormat::GeoJson, &[point_feature(), point_feature()]);
+        let features = FeatureIterator::new(output.as_bytes()).collect::<Result<Vec<_>, _>>();
+        assert_eq!(features.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_write_wkt() {
+        assert_eq!(
+            write_string(ConvertFormat::Wkt, &[point_feature()]),
+            "POINT(1 2)\n"
+        );
+        assert_eq!(
+            write_string(ConvertFormat::Wkt, &[point_feature(), point_feature()]),
+            "GEOMETRYCOLLECTION(POINT(1 2),POINT(1 2))\n"
+        );
+    }
+
+    #[test]
+    fn test_write_csv() {
+        assert_eq!(
+            write_string(ConvertFormat::Csv, &[point_feature()]),
+            "wkt,id,name\nPOINT(1 2),1,\"a, b\"\n"
+        );
+    }
+
+    #[test]
+    fn test_write_polyline() {
+        let line = Feature::new(
+            Geometry::LineString(LineString::from(vec![
+                (-120.2, 38.5),
+                (-120.95, 40.7),
+                (-126.453, 43.252),
+            ])),
+            Properties::new(),
+        );
+        assert_eq!(
+            write_string(ConvertFormat::Polyline { precision: 5 }, &[line]),
+            "_p~iF~ps|U_ulLnnqC_mqNvxq`@\n"
+        );
+        assert!(features_polyline(&[point_feature()]).is_err());
+    }
+}
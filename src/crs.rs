@@ -0,0 +1,527 @@
+//! Coordinate reference systems read from .prj files and transformed to lon/lat
+
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4};
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use geo::{Coord, Geometry, MapCoords};
+
+/// Iterations used to solve for latitude in inverse projections
+const MAX_ITERATIONS: usize = 15;
+const CONVERGENCE: f64 = 1e-12;
+
+/// Value in a WKT CRS definition
+#[derive(Debug, Clone, PartialEq)]
+enum WktValue {
+    Node(WktNode),
+    Text(String),
+    Number(f64),
+}
+
+/// Keyword with bracketed values in a WKT CRS definition, like UNIT["metre",1]
+#[derive(Debug, Clone, PartialEq)]
+struct WktNode {
+    keyword: String,
+    values: Vec<WktValue>,
+}
+
+impl WktNode {
+    /// Parse a WKT CRS string into a tree of nodes
+    fn parse(wkt: &str) -> Result<WktNode> {
+        let mut chars = wkt.trim().chars().peekable();
+        let node = WktNode::parse_node(&mut chars)?;
+        match chars.find(|c| !c.is_whitespace()) {
+            Some(c) => Err(anyhow::anyhow!("Unexpected character {} after CRS", c)),
+            None => Ok(node),
+        }
+    }
+
+    fn parse_node<I: Iterator<Item = char>>(chars: &mut std::iter::Peekable<I>) -> Result<WktNode> {
+        let keyword: String =
+            std::iter::from_fn(|| chars.next_if(|c| c.is_alphanumeric() || *c == '_')).collect();
+        if keyword.is_empty() {
+            return Err(anyhow::anyhow!("Expected a CRS keyword"));
+        }
+        skip_whitespace(chars);
+        if chars.next_if(|c| *c == '[' || *c == '(').is_none() {
+            return Ok(WktNode {
+                keyword,
+                values: vec![],
+            });
+        }
+
+        let mut values = vec![];
+        loop {
+            skip_whitespace(chars);
+            let value = match chars.peek() {
+                Some('"') => {
+                    chars.next();
+                    let text: String =
+                        std::iter::from_fn(|| chars.next_if(|c| *c != '"')).collect();
+                    chars.next();
+                    WktValue::Text(text)
+                }
+                Some(c) if c.is_ascii_digit() || *c == '-' || *c == '+' || *c == '.' => {
+                    let num: String = std::iter::from_fn(|| {
+                        chars.next_if(|c| c.is_ascii_digit() || "-+.eE".contains(*c))
+                    })
+                    .collect();
+                    WktValue::Number(
+                        num.parse()
+                            .with_context(|| format!("Invalid number in CRS: {}", num))?,
+                    )
+                }
+                Some(_) => WktValue::Node(WktNode::parse_node(chars)?),
+                None => return Err(anyhow::anyhow!("Unexpected end of CRS")),
+            };
+            values.push(value);
+            skip_whitespace(chars);
+            match chars.next() {
+                Some(',') => continue,
+                Some(']') | Some(')') => break,
+                _ => return Err(anyhow::anyhow!("Expected , or ] in {} of CRS", keyword)),
+            }
+        }
+        Ok(WktNode { keyword, values })
+    }
+
+    /// Get the first child node with a keyword
+    fn child(&self, keyword: &str) -> Option<&WktNode> {
+        self.values.iter().find_map(|v| match v {
+            WktValue::Node(node) if node.keyword.eq_ignore_ascii_case(keyword) => Some(node),
+            _ => None,
+        })
+    }
+
+    /// Get the name of the node, which is its first value if it's text
+    fn name(&self) -> Option<&str> {
+        match self.values.first() {
+            Some(WktValue::Text(text)) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Get a numeric value by its position, skipping the name
+    fn number(&self, idx: usize) -> Option<f64> {
+        self.values
+            .iter()
+            .filter_map(|v| match v {
+                WktValue::Number(num) => Some(*num),
+                _ => None,
+            })
+            .nth(idx)
+    }
+
+    /// Get the value of a named PARAMETER node, ignoring case
+    fn parameter(&self, name: &str) -> Option<f64> {
+        self.values.iter().find_map(|v| match v {
+            WktValue::Node(node)
+                if node.keyword.eq_ignore_ascii_case("PARAMETER")
+                    && node.name().is_some_and(|n| n.eq_ignore_ascii_case(name)) =>
+            {
+                node.number(0)
+            }
+            _ => None,
+        })
+    }
+}
+
+fn skip_whitespace<I: Iterator<Item = char>>(chars: &mut std::iter::Peekable<I>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+/// Ellipsoid defined by its semi-major axis and eccentricity squared
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Ellipsoid {
+    a: f64,
+    e2: f64,
+}
+
+impl Ellipsoid {
+    fn from_inverse_flattening(a: f64, inv_f: f64) -> Ellipsoid {
+        // Inverse flattening of 0 is used for spheres
+        let f = if inv_f == 0. { 0. } else { 1. / inv_f };
+        Ellipsoid {
+            a,
+            e2: f * (2. - f),
+        }
+    }
+
+    /// Distance along the meridian from the equator to a latitude
+    fn meridian_distance(&self, lat: f64) -> f64 {
+        let (e2, e4, e6) = (self.e2, self.e2.powi(2), self.e2.powi(3));
+        self.a
+            * ((1. - e2 / 4. - 3. * e4 / 64. - 5. * e6 / 256.) * lat
+                - (3. * e2 / 8. + 3. * e4 / 32. + 45. * e6 / 1024.) * (2. * lat).sin()
+                + (15. * e4 / 256. + 45. * e6 / 1024.) * (4. * lat).sin()
+                - (35. * e6 / 3072.) * (6. * lat).sin())
+    }
+}
+
+/// Parameters shared by projected coordinate systems
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProjectionParams {
+    ellipsoid: Ellipsoid,
+    /// Longitude and latitude of the origin in radians
+    origin: (f64, f64),
+    scale_factor: f64,
+    /// False origin in the linear unit of the CRS
+    false_easting: f64,
+    false_northing: f64,
+}
+
+/// Coordinate reference system that can be transformed to lon/lat
+#[derive(Debug, Clone, PartialEq)]
+pub enum Crs {
+    /// Geographic coordinates with a prime meridian offset and angular unit in degrees
+    Geographic {
+        /// Longitude of the prime meridian in degrees
+        prime_meridian: f64,
+        /// Size of the angular unit in degrees
+        unit: f64,
+    },
+    /// Transverse mercator, including UTM zones
+    TransverseMercator {
+        /// Ellipsoid, origin and false origin
+        params: ProjectionParams,
+        /// Size of the linear unit in metres
+        unit: f64,
+    },
+    /// Lambert conformal conic with standard parallels in radians
+    LambertConformalConic {
+        /// Ellipsoid, origin and false origin
+        params: ProjectionParams,
+        /// Standard parallels in radians
+        parallels: (f64, f64),
+        /// Size of the linear unit in metres
+        unit: f64,
+    },
+}
+
+impl Crs {
+    /// Read the CRS from a WKT definition like the contents of a shapefile .prj
+    pub fn from_wkt(wkt: &str) -> Result<Crs> {
+        let root = WktNode::parse(wkt).context("Could not parse CRS")?;
+        let crs_name = root.name().unwrap_or_default().to_string();
+        let geogcs = if root.keyword.eq_ignore_ascii_case("GEOGCS") {
+            &root
+        } else {
+            root.child("GEOGCS")
+                .with_context(|| format!("Unsupported CRS {}: no geographic CRS", crs_name))?
+        };
+        let prime_meridian = geogcs
+            .child("PRIMEM")
+            .and_then(|p| p.number(0))
+            .unwrap_or(0.);
+        let angular_unit = geogcs
+            .child("UNIT")
+            .and_then(|u| u.number(0))
+            .map_or(1., |u| u.to_degrees());
+
+        if root.keyword.eq_ignore_ascii_case("GEOGCS") {
+            return Ok(Crs::Geographic {
+                prime_meridian,
+                unit: angular_unit,
+            });
+        }
+        if !root.keyword.eq_ignore_ascii_case("PROJCS") {
+            return Err(anyhow::anyhow!(
+                "Unsupported CRS type {} for {}",
+                root.keyword,
+                crs_name
+            ));
+        }
+
+        let spheroid = geogcs
+            .child("DATUM")
+            .and_then(|d| d.child("SPHEROID"))
+            .with_context(|| format!("Unsupported CRS {}: no spheroid", crs_name))?;
+        let ellipsoid = match (spheroid.number(0), spheroid.number(1)) {
+            (Some(a), Some(inv_f)) => Ellipsoid::from_inverse_flattening(a, inv_f),
+            _ => return Err(anyhow::anyhow!("Invalid spheroid in CRS {}", crs_name)),
+        };
+        let angle = |name: &str| (root.parameter(name).unwrap_or(0.) * angular_unit).to_radians();
+        let params = ProjectionParams {
+            ellipsoid,
+            origin: (angle("central_meridian"), angle("latitude_of_origin")),
+            scale_factor: root.parameter("scale_factor").unwrap_or(1.),
+            false_easting: root.parameter("false_easting").unwrap_or(0.),
+            false_northing: root.parameter("false_northing").unwrap_or(0.),
+        };
+        let unit = root.child("UNIT").and_then(|u| u.number(0)).unwrap_or(1.);
+
+        let projection = root
+            .child("PROJECTION")
+            .and_then(|p| p.name())
+            .with_context(|| format!("Unsupported CRS {}: no projection", crs_name))?;
+        match projection.to_ascii_lowercase().as_ref() {
+            "transverse_mercator" => Ok(Crs::TransverseMercator { params, unit }),
+            "lambert_conformal_conic" | "lambert_conformal_conic_2sp" => {
+                let lat1 = angle("standard_parallel_1");
+                Ok(Crs::LambertConformalConic {
+                    params,
+                    parallels: (
+                        lat1,
+                        root.parameter("standard_parallel_2")
+                            .map_or(lat1, |_| angle("standard_parallel_2")),
+                    ),
+                    unit,
+                })
+            }
+            "lambert_conformal_conic_1sp" => Ok(Crs::LambertConformalConic {
+                params,
+                parallels: (params.origin.1, params.origin.1),
+                unit,
+            }),
+            _ => Err(anyhow::anyhow!(
+                "Unsupported projection {} in CRS {}. Supported projections are Transverse_Mercator and Lambert_Conformal_Conic",
+                projection,
+                crs_name
+            )),
+        }
+    }
+
+    /// Whether coordinates are already lon/lat in degrees from Greenwich
+    pub fn is_lon_lat(&self) -> bool {
+        match self {
+            // Degree units are often rounded in .prj files
+            Crs::Geographic {
+                prime_meridian,
+                unit,
+            } => *prime_meridian == 0. && (unit - 1.).abs() < CONVERGENCE,
+            _ => false,
+        }
+    }
+
+    /// Transform a coordinate to lon/lat degrees. Datum shifts are not applied, so datums
+    /// like NAD83 are treated as equivalent to WGS84
+    pub fn to_lon_lat(&self, coord: Coord<f64>) -> Coord<f64> {
+        let (lon, lat) = match self {
+            Crs::Geographic {
+                prime_meridian,
+                unit,
+            } => {
+                return Coord {
+                    x: coord.x * unit + prime_meridian,
+                    y: coord.y * unit,
+                }
+            }
+            Crs::TransverseMercator { params, unit } => inverse_transverse_mercator(
+                params,
+                (coord.x - params.false_easting) * unit,
+                (coord.y - params.false_northing) * unit,
+            ),
+            Crs::LambertConformalConic {
+                params,
+                parallels,
+                unit,
+            } => inverse_lambert_conformal_conic(
+                params,
+                *parallels,
+                (coord.x - params.false_easting) * unit,
+                (coord.y - params.false_northing) * unit,
+            ),
+        };
+        Coord {
+            x: lon.to_degrees(),
+            y: lat.to_degrees(),
+        }
+    }
+
+    /// Transform all coordinates of a geometry to lon/lat
+    pub fn geom_to_lon_lat(&self, geom: Geometry<f64>) -> Geometry<f64> {
+        if self.is_lon_lat() {
+            return geom;
+        }
+        geom.map_coords(|c| self.to_lon_lat(c))
+    }
+}
+
+/// Read the CRS from the .prj file next to a shapefile if it exists
+pub fn read_prj(shp_path: &str) -> Result<Option<Crs>> {
+    let prj_path = Path::new(shp_path).with_extension("prj");
+    match read_prj_wkt(&prj_path)? {
+        Some(wkt) => Crs::from_wkt(&wkt)
+            .with_context(|| format!("Could not read the CRS in {}", prj_path.display()))
+            .map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Read the name of the CRS in a shapefile's .prj file if it exists, which doesn't need to be
+/// supported, or None if the file can't be parsed
+pub fn read_prj_name(shp_path: &str) -> Result<Option<String>> {
+    let prj_path = Path::new(shp_path).with_extension("prj");
+    Ok(read_prj_wkt(&prj_path)?.and_then(|wkt| {
+        let root = WktNode::parse(&wkt).ok()?;
+        Some(root.name().unwrap_or(&root.keyword).to_string())
+    }))
+}
+
+/// Read the WKT contents of a .prj file if it exists and isn't empty
+fn read_prj_wkt(prj_path: &Path) -> Result<Option<String>> {
+    if !prj_path.exists() {
+        return Ok(None);
+    }
+    let wkt = fs::read_to_string(prj_path)
+        .with_context(|| format!("There was an error reading {}", prj_path.display()))?;
+    Ok(Some(wkt).filter(|wkt| !wkt.trim().is_empty()))
+}
+
+/// Get lon/lat in radians from transverse mercator coordinates relative to the false origin
+fn inverse_transverse_mercator(params: &ProjectionParams, x: f64, y: f64) -> (f64, f64) {
+    let Ellipsoid { a, e2 } = params.ellipsoid;
+    let k0 = params.scale_factor;
+    let ep2 = e2 / (1. - e2);
+
+    let m = params.ellipsoid.meridian_distance(params.origin.1) + y / k0;
+    let mu = m / (a * (1. - e2 / 4. - 3. * e2.powi(2) / 64. - 5. * e2.powi(3) / 256.));
+    let e1 = (1. - (1. - e2).sqrt()) / (1. + (1. - e2).sqrt());
+    let lat1 = mu
+        + (3. * e1 / 2. - 27. * e1.powi(3) / 32.) * (2. * mu).sin()
+        + (21. * e1.powi(2) / 16. - 55. * e1.powi(4) / 32.) * (4. * mu).sin()
+        + (151. * e1.powi(3) / 96.) * (6. * mu).sin()
+        + (1097. * e1.powi(4) / 512.) * (8. * mu).sin();
+
+    let sin_lat1 = lat1.sin();
+    let c1 = ep2 * lat1.cos().powi(2);
+    let t1 = lat1.tan().powi(2);
+    let n1 = a / (1. - e2 * sin_lat1.powi(2)).sqrt();
+    let r1 = a * (1. - e2) / (1. - e2 * sin_lat1.powi(2)).powf(1.5);
+    let d = x / (n1 * k0);
+
+    let lat = lat1
+        - (n1 * lat1.tan() / r1)
+            * (d.powi(2) / 2.
+                - (5. + 3. * t1 + 10. * c1 - 4. * c1.powi(2) - 9. * ep2) * d.powi(4) / 24.
+                + (61. + 90. * t1 + 298. * c1 + 45. * t1.powi(2) - 252. * ep2 - 3. * c1.powi(2))
+                    * d.powi(6)
+                    / 720.);
+    let lon = params.origin.0
+        + (d - (1. + 2. * t1 + c1) * d.powi(3) / 6.
+            + (5. - 2. * c1 + 28. * t1 - 3. * c1.powi(2) + 8. * ep2 + 24. * t1.powi(2))
+                * d.powi(5)
+                / 120.)
+            / lat1.cos();
+    (lon, lat)
+}
+
+/// Get lon/lat in radians from lambert conformal conic coordinates relative to the false origin
+fn inverse_lambert_conformal_conic(
+    params: &ProjectionParams,
+    parallels: (f64, f64),
+    x: f64,
+    y: f64,
+) -> (f64, f64) {
+    let Ellipsoid { a, e2 } = params.ellipsoid;
+    let e = e2.sqrt();
+    let m = |lat: f64| lat.cos() / (1. - e2 * lat.sin().powi(2)).sqrt();
+    let t = |lat: f64| {
+        (FRAC_PI_4 - lat / 2.).tan() / ((1. - e * lat.sin()) / (1. + e * lat.sin())).powf(e / 2.)
+    };
+
+    let (lat1, lat2) = parallels;
+    let n = if (lat1 - lat2).abs() < CONVERGENCE {
+        lat1.sin()
+    } else {
+        (m(lat1).ln() - m(lat2).ln()) / (t(lat1).ln() - t(lat2).ln())
+    };
+    let big_f = m(lat1) / (n * t(lat1).powf(n));
+    let scale = a * big_f * params.scale_factor;
+    let rho0 = scale * t(params.origin.1).powf(n);
+
+    let dy = rho0 - y;
+    let rho = n.signum() * (x.powi(2) + dy.powi(2)).sqrt();
+    let theta = (n.signum() * x).atan2(n.signum() * dy);
+    let t_val = (rho / scale).powf(1. / n);
+
+    let mut lat = FRAC_PI_2 - 2. * t_val.atan();
+    for _ in 0..MAX_ITERATIONS {
+        let next = FRAC_PI_2
+            - 2. * (t_val * ((1. - e * lat.sin()) / (1. + e * lat.sin())).powf(e / 2.)).atan();
+        let done = (next - lat).abs() < CONVERGENCE;
+        lat = next;
+        if done {
+            break;
+        }
+    }
+    (theta / n + params.origin.0, lat)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use float_cmp::approx_eq;
+
+    const CLARKE_1866: &str = r#"GEOGCS["NAD27",DATUM["North_American_Datum_1927",SPHEROID["Clarke 1866",6378206.4,294.978698213898]],PRIMEM["Greenwich",0],UNIT["degree",0.0174532925199433]]"#;
+
+    #[test]
+    fn test_geographic() {
+        let crs = Crs::from_wkt(include_str!("../fixtures/input_area.prj")).unwrap();
+        assert!(crs.is_lon_lat());
+        let crs = Crs::from_wkt(CLARKE_1866).unwrap();
+        assert!(crs.is_lon_lat());
+    }
+
+    #[test]
+    fn test_transverse_mercator() {
+        // Example from Snyder's Map Projections: A Working Manual
+        let crs = Crs::from_wkt(&format!(
+            r#"PROJCS["TM",{},PROJECTION["Transverse_Mercator"],PARAMETER["central_meridian",-75],PARAMETER["scale_factor",0.9996],UNIT["metre",1]]"#,
+            CLARKE_1866
+        ))
+        .unwrap();
+        let coord = crs.to_lon_lat(Coord {
+            x: 127106.5,
+            y: 4484124.4,
+        });
+        assert!(approx_eq!(f64, coord.x, -73.5, epsilon = 1e-6));
+        assert!(approx_eq!(f64, coord.y, 40.5, epsilon = 1e-6));
+    }
+
+    #[test]
+    fn test_lambert_conformal_conic() {
+        // Example from Snyder's Map Projections: A Working Manual
+        let crs = Crs::from_wkt(&format!(
+            r#"PROJCS["LCC",{},PROJECTION["Lambert_Conformal_Conic_2SP"],PARAMETER["standard_parallel_1",33],PARAMETER["standard_parallel_2",45],PARAMETER["latitude_of_origin",23],PARAMETER["central_meridian",-96],UNIT["metre",1]]"#,
+            CLARKE_1866
+        ))
+        .unwrap();
+        let coord = crs.to_lon_lat(Coord {
+            x: 1894410.9,
+            y: 1564649.5,
+        });
+        assert!(approx_eq!(f64, coord.x, -75., epsilon = 1e-6));
+        assert!(approx_eq!(f64, coord.y, 35., epsilon = 1e-6));
+    }
+
+    #[test]
+    fn test_us_survey_feet() {
+        // NAD83 / Illinois East (ftUS), whose false origin maps back to the projection origin
+        let crs = Crs::from_wkt(r#"PROJCS["NAD_1983_StatePlane_Illinois_East_FIPS_1201_Feet",GEOGCS["GCS_North_American_1983",DATUM["D_North_American_1983",SPHEROID["GRS_1980",6378137.0,298.257222101]],PRIMEM["Greenwich",0.0],UNIT["Degree",0.0174532925199433]],PROJECTION["Transverse_Mercator"],PARAMETER["False_Easting",984250.0],PARAMETER["False_Northing",0.0],PARAMETER["Central_Meridian",-88.33333333333333],PARAMETER["Scale_Factor",0.999975],PARAMETER["Latitude_Of_Origin",36.66666666666666],UNIT["Foot_US",0.3048006096012192]]"#).unwrap();
+        let coord = crs.to_lon_lat(Coord { x: 984250., y: 0. });
+        assert!(approx_eq!(f64, coord.x, -88.33333333333333, epsilon = 1e-6));
+        assert!(approx_eq!(f64, coord.y, 36.66666666666666, epsilon = 1e-6));
+    }
+
+    #[test]
+    fn test_read_prj_unsupported() {
+        let shp_path = "./fixtures/input_unsupported_crs.shp";
+        assert!(read_prj(shp_path).is_err());
+        assert_eq!(
+            read_prj_name(shp_path).unwrap().as_deref(),
+            Some("WGS_1984_Web_Mercator_Auxiliary_Sphere")
+        );
+    }
+
+    #[test]
+    fn test_unsupported_projection() {
+        let err = Crs::from_wkt(&format!(
+            r#"PROJCS["Robinson",{},PROJECTION["Robinson"],UNIT["metre",1]]"#,
+            CLARKE_1866
+        ))
+        .unwrap_err();
+        assert!(err.to_string().contains("Unsupported projection Robinson"));
+    }
+}
@@ -0,0 +1,477 @@
+//! Expressions for filtering features by their properties
+
+use std::cmp::Ordering;
+use std::iter::Peekable;
+use std::str::{Chars, FromStr};
+
+use anyhow::{Context, Result};
+
+use crate::properties::{PropValue, Properties};
+
+/// Token in a filter expression
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// Property name or keyword
+    Word(String),
+    /// Double-quoted property name, which can include spaces or match keywords
+    QuotedWord(String),
+    Text(String),
+    Number(f64),
+    Op(String),
+    LParen,
+    RParen,
+    Comma,
+}
+
+/// Split a filter expression into tokens
+fn tokenize(expr: &str) -> Result<Vec<Token>> {
+    let mut chars = expr.chars().peekable();
+    let mut tokens = vec![];
+    while let Some(&c) = chars.peek() {
+        let token = match c {
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '(' | ')' | ',' => {
+                chars.next();
+                match c {
+                    '(' => Token::LParen,
+                    ')' => Token::RParen,
+                    _ => Token::Comma,
+                }
+            }
+            '\'' => Token::Text(read_quoted(&mut chars, '\'')?),
+            '"' => Token::QuotedWord(read_quoted(&mut chars, '"')?),
+            '=' | '!' | '<' | '>' => {
+                let op: String =
+                    std::iter::from_fn(|| chars.next_if(|c| "=!<>".contains(*c))).collect();
+                if !["=", "!=", "<>", "<", "<=", ">", ">="].contains(&op.as_str()) {
+                    return Err(anyhow::anyhow!("Invalid operator {}", op));
+                }
+                Token::Op(op)
+            }
+            c if c.is_ascii_digit() || c == '-' || c == '.' => {
+                let num: String = std::iter::from_fn(|| {
+                    chars.next_if(|c| c.is_ascii_digit() || "-+.eE".contains(*c))
+                })
+                .collect();
+                Token::Number(
+                    num.parse()
+                        .with_context(|| format!("Invalid number {}", num))?,
+                )
+            }
+            c if c.is_alphabetic() || c == '_' => Token::Word(
+                std::iter::from_fn(|| chars.next_if(|c| c.is_alphanumeric() || *c == '_'))
+                    .collect(),
+            ),
+            c => return Err(anyhow::anyhow!("Unexpected character {}", c)),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Read text up to a closing quote, where doubled quotes are escaped quotes as in SQL
+fn read_quoted(chars: &mut Peekable<Chars>, quote: char) -> Result<String> {
+    chars.next();
+    let mut text = String::new();
+    loop {
+        match chars.next() {
+            Some(c) if c == quote && chars.next_if_eq(&quote).is_some() => text.push(quote),
+            Some(c) if c == quote => return Ok(text),
+            Some(c) => text.push(c),
+            None => return Err(anyhow::anyhow!("Missing closing {}", quote)),
+        }
+    }
+}
+
+/// Comparison operator between a property and a value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    /// Equal, `=`
+    Eq,
+    /// Not equal, `!=` or `<>`
+    Ne,
+    /// Less than, `<`
+    Lt,
+    /// Less than or equal, `<=`
+    Le,
+    /// Greater than, `>`
+    Gt,
+    /// Greater than or equal, `>=`
+    Ge,
+}
+
+impl CompareOp {
+    fn test(self, ord: Ordering) -> bool {
+        match self {
+            CompareOp::Eq => ord == Ordering::Equal,
+            CompareOp::Ne => ord != Ordering::Equal,
+            CompareOp::Lt => ord == Ordering::Less,
+            CompareOp::Le => ord != Ordering::Greater,
+            CompareOp::Gt => ord == Ordering::Greater,
+            CompareOp::Ge => ord != Ordering::Less,
+        }
+    }
+}
+
+/// Expression over feature properties, like a SQL WHERE clause
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    /// Both expressions match
+    And(Box<Filter>, Box<Filter>),
+    /// Either expression matches
+    Or(Box<Filter>, Box<Filter>),
+    /// The expression doesn't match
+    Not(Box<Filter>),
+    /// Property compared to a value
+    Compare {
+        /// Name of the property
+        property: String,
+        /// Comparison operator
+        op: CompareOp,
+        /// Value compared to
+        value: PropValue,
+    },
+    /// Case-insensitive pattern where % matches any characters and _ matches one character
+    Like {
+        /// Name of the property
+        property: String,
+        /// Pattern to match
+        pattern: String,
+    },
+    /// Property equal to any of the values
+    In {
+        /// Name of the property
+        property: String,
+        /// Values to match
+        values: Vec<PropValue>,
+    },
+    /// Property missing or null
+    IsNull {
+        /// Name of the property
+        property: String,
+    },
+}
+
+impl FromStr for Filter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Filter> {
+        let parse = || {
+            let mut parser = Parser {
+                tokens: tokenize(s)?,
+                pos: 0,
+            };
+            let filter = parser.parse_or()?;
+            match parser.next() {
+                Some(token) => Err(anyhow::anyhow!("Unexpected {:?}", token)),
+                None => Ok(filter),
+            }
+        };
+        parse().with_context(|| format!("Invalid filter expression: {}", s))
+    }
+}
+
+impl Filter {
+    /// Check whether a feature's properties match the filter
+    pub fn matches(&self, properties: &Properties) -> bool {
+        self.eval(properties) == Some(true)
+    }
+
+    /// Evaluate the filter with SQL logic, where comparisons with null are unknown (None)
+    fn eval(&self, properties: &Properties) -> Option<bool> {
+        match self {
+            Filter::And(a, b) => match (a.eval(properties), b.eval(properties)) {
+                (Some(false), _) | (_, Some(false)) => Some(false),
+                (Some(true), Some(true)) => Some(true),
+                _ => None,
+            },
+            Filter::Or(a, b) => match (a.eval(properties), b.eval(properties)) {
+                (Some(true), _) | (_, Some(true)) => Some(true),
+                (Some(false), Some(false)) => Some(false),
+                _ => None,
+            },
+            Filter::Not(f) => f.eval(properties).map(|v| !v),
+            Filter::Compare {
+                property,
+                op,
+                value,
+            } => compare(get_property(properties, property)?, value).map(|ord| op.test(ord)),
+            Filter::Like { property, pattern } => {
+                let text = get_property(properties, property)?.to_string();
+                Some(like(&text.to_lowercase(), &pattern.to_lowercase()))
+            }
+            Filter::In { property, values } => {
+                let prop = get_property(properties, property)?;
+                Some(
+                    values
+                        .iter()
+                        .any(|v| compare(prop, v) == Some(Ordering::Equal)),
+                )
+            }
+            Filter::IsNull { property } => Some(get_property(properties, property).is_none()),
+        }
+    }
+}
+
+/// Get a non-null property by name, falling back to a case-insensitive match since shapefile
+/// fields are often uppercase
+fn get_property<'a>(properties: &'a Properties, name: &str) -> Option<&'a PropValue> {
+    properties
+        .get(name)
+        .or_else(|| {
+            properties
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v)
+        })
+        .filter(|v| **v != PropValue::Null)
+}
+
+/// Compare a property with a value, comparing text as numbers if the other side is a number
+fn compare(prop: &PropValue, value: &PropValue) -> Option<Ordering> {
+    match (prop, value) {
+        (PropValue::Number(a), PropValue::Number(b)) => a.partial_cmp(b),
+        (PropValue::String(a), PropValue::Number(b)) => {
+            a.trim().parse::<f64>().ok()?.partial_cmp(b)
+        }
+        (PropValue::Number(a), PropValue::String(b)) => {
+            a.partial_cmp(&b.trim().parse::<f64>().ok()?)
+        }
+        (PropValue::String(a), PropValue::String(b)) => Some(a.cmp(b)),
+        (PropValue::Bool(a), PropValue::Bool(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+/// Match text against a LIKE pattern, backtracking to the last % on a mismatch
+fn like(text: &str, pattern: &str) -> bool {
+    let text: Vec<char> = text.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    let (mut t, mut p) = (0, 0);
+    let mut last_wildcard: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('%') => {
+                last_wildcard = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '_' || c == text[t] => {
+                t += 1;
+                p += 1;
+            }
+            _ => match last_wildcard {
+                Some((wp, wt)) => {
+                    last_wildcard = Some((wp, wt + 1));
+                    p = wp + 1;
+                    t = wt + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '%')
+}
+
+/// Recursive descent parser for filter expressions, with OR binding looser than AND
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// Consume the next token if it's the keyword, ignoring case
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.tokens.get(self.pos) {
+            Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(anyhow::anyhow!(
+                "Expected {:?}, found {:?}",
+                expected,
+                token
+            )),
+            None => Err(anyhow::anyhow!("Expected {:?}", expected)),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Filter> {
+        let mut filter = self.parse_and()?;
+        while self.keyword("OR") {
+            filter = Filter::Or(Box::new(filter), Box::new(self.parse_and()?));
+        }
+        Ok(filter)
+    }
+
+    fn parse_and(&mut self) -> Result<Filter> {
+        let mut filter = self.parse_not()?;
+        while self.keyword("AND") {
+            filter = Filter::And(Box::new(filter), Box::new(self.parse_not()?));
+        }
+        Ok(filter)
+    }
+
+    fn parse_not(&mut self) -> Result<Filter> {
+        if self.keyword("NOT") {
+            return Ok(Filter::Not(Box::new(self.parse_not()?)));
+        }
+        if self.tokens.get(self.pos) == Some(&Token::LParen) {
+            self.pos += 1;
+            let filter = self.parse_or()?;
+            self.expect(Token::RParen)?;
+            return Ok(filter);
+        }
+        self.parse_condition()
+    }
+
+    /// Parse a condition on a property, like `name LIKE 'Chi%'` or `population > 10000`
+    fn parse_condition(&mut self) -> Result<Filter> {
+        let property = match self.next() {
+            Some(Token::Word(w)) | Some(Token::QuotedWord(w)) => w,
+            Some(token) => return Err(anyhow::anyhow!("Expected a property, found {:?}", token)),
+            None => return Err(anyhow::anyhow!("Expected a property")),
+        };
+
+        if self.keyword("IS") {
+            let negated = self.keyword("NOT");
+            if !self.keyword("NULL") {
+                return Err(anyhow::anyhow!("Expected NULL after IS"));
+            }
+            return Ok(negate(Filter::IsNull { property }, negated));
+        }
+        let negated = self.keyword("NOT");
+        if self.keyword("LIKE") {
+            return match self.next() {
+                Some(Token::Text(pattern)) => {
+                    Ok(negate(Filter::Like { property, pattern }, negated))
+                }
+                _ => Err(anyhow::anyhow!("Expected a quoted pattern after LIKE")),
+            };
+        }
+        if self.keyword("IN") {
+            self.expect(Token::LParen)?;
+            let mut values = vec![self.parse_value()?];
+            while self.tokens.get(self.pos) == Some(&Token::Comma) {
+                self.pos += 1;
+                values.push(self.parse_value()?);
+            }
+            self.expect(Token::RParen)?;
+            return Ok(negate(Filter::In { property, values }, negated));
+        }
+        if negated {
+            return Err(anyhow::anyhow!("Expected LIKE or IN after NOT"));
+        }
+
+        let op = match self.next() {
+            Some(Token::Op(op)) => match op.as_str() {
+                "=" => CompareOp::Eq,
+                "!=" | "<>" => CompareOp::Ne,
+                "<" => CompareOp::Lt,
+                "<=" => CompareOp::Le,
+                ">" => CompareOp::Gt,
+                _ => CompareOp::Ge,
+            },
+            _ => return Err(anyhow::anyhow!("Expected an operator after {}", property)),
+        };
+        Ok(Filter::Compare {
+            property,
+            op,
+            value: self.parse_value()?,
+        })
+    }
+
+    fn parse_value(&mut self) -> Result<PropValue> {
+        match self.next() {
+            Some(Token::Text(text)) => Ok(PropValue::String(text)),
+            Some(Token::Number(num)) => Ok(PropValue::Number(num)),
+            Some(Token::Word(w)) if w.eq_ignore_ascii_case("TRUE") => Ok(PropValue::Bool(true)),
+            Some(Token::Word(w)) if w.eq_ignore_ascii_case("FALSE") => Ok(PropValue::Bool(false)),
+            Some(Token::Word(w)) if w.eq_ignore_ascii_case("NULL") => Ok(PropValue::Null),
+            Some(token) => Err(anyhow::anyhow!("Expected a value, found {:?}", token)),
+            None => Err(anyhow::anyhow!("Expected a value")),
+        }
+    }
+}
+
+/// Wrap a filter in NOT if it was negated
+fn negate(filter: Filter, negated: bool) -> Filter {
+    if negated {
+        Filter::Not(Box::new(filter))
+    } else {
+        filter
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn properties() -> Properties {
+        let mut properties = Properties::new();
+        properties.insert("NAME".to_string(), PropValue::String("Chicago".to_string()));
+        properties.insert("population".to_string(), PropValue::Number(2_746_388.));
+        properties.insert("state".to_string(), PropValue::String("IL".to_string()));
+        properties.insert("fips".to_string(), PropValue::String("17031".to_string()));
+        properties.insert("area code".to_string(), PropValue::Null);
+        properties
+    }
+
+    fn matches(expr: &str) -> bool {
+        expr.parse::<Filter>().unwrap().matches(&properties())
+    }
+
+    #[test]
+    fn test_compare() {
+        assert!(matches("population > 10000 AND state = 'IL'"));
+        assert!(!matches("population <= 10000"));
+        assert!(matches("fips = 17031"));
+        assert!(matches("name != 'Springfield'"));
+        assert!(matches(
+            "state = 'WI' OR (state = 'IL' AND NOT population < 1000)"
+        ));
+    }
+
+    #[test]
+    fn test_like_in_null() {
+        assert!(matches("name LIKE 'chi%'"));
+        assert!(matches("name LIKE '_hicag_'"));
+        assert!(!matches("name NOT LIKE '%go'"));
+        assert!(matches("state IN ('IL', 'IN', 'WI')"));
+        assert!(!matches("state NOT IN ('IL')"));
+        assert!(matches("\"area code\" IS NULL AND missing IS NULL"));
+        assert!(!matches("\"area code\" = 312 OR \"area code\" != 312"));
+    }
+
+    #[test]
+    fn test_like() {
+        assert!(like("chicago", "%ca%o"));
+        assert!(like("", "%"));
+        assert!(!like("chicago", "chi"));
+        assert!(like("50%", "50%"));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!("population >".parse::<Filter>().is_err());
+        assert!("state = 'IL".parse::<Filter>().is_err());
+        assert!("state == 'IL'".parse::<Filter>().is_err());
+        assert!("(state = 'IL'".parse::<Filter>().is_err());
+        assert!("state = 'IL' extra".parse::<Filter>().is_err());
+    }
+}
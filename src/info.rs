@@ -0,0 +1,244 @@
+//! Summaries of an input's features, extent, CRS and fields
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use anyhow::Result;
+use geo::{BoundingRect, CoordsIter, Geometry, Rect};
+
+use crate::properties::PropValue;
+use crate::reader::{GeoReader, SkipReason};
+
+/// Summary of an input's features, extent, CRS and attribute fields
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatasetInfo {
+    /// Name of the input format
+    pub format: String,
+    /// Number of features, including null geometries and unreadable features
+    pub feature_count: usize,
+    /// Number of features of each geometry type
+    pub geometry_types: BTreeMap<String, usize>,
+    /// Number of coordinates in all geometries
+    pub vertex_count: usize,
+    /// Extent of the features as they're read
+    pub bbox: Option<Rect<f64>>,
+    /// Whether features are read in WGS84 lon/lat rather than the input's own coordinates
+    pub is_lon_lat: bool,
+    /// Name of the CRS declared in the input
+    pub crs: Option<String>,
+    /// Names and types of attribute fields
+    pub fields: Vec<(String, String)>,
+    /// Number of features without a geometry
+    pub null_geometry_count: usize,
+    /// Number of features that couldn't be read
+    pub skipped_count: usize,
+}
+
+impl DatasetInfo {
+    /// Read every feature in an input to summarize it
+    pub fn read(reader: &dyn GeoReader, file_path: &str) -> Result<DatasetInfo> {
+        // Read metadata first since features can only be read once from stdin
+        let crs = reader.crs(file_path)?;
+        let declared_fields = reader.fields(file_path)?;
+        let is_lon_lat = reader.is_lon_lat(file_path);
+
+        let mut info = DatasetInfo {
+            format: reader.names()[0].to_string(),
+            feature_count: 0,
+            geometry_types: BTreeMap::new(),
+            vertex_count: 0,
+            bbox: None,
+            is_lon_lat,
+            crs,
+            fields: vec![],
+            null_geometry_count: 0,
+            skipped_count: 0,
+        };
+        let mut field_types: BTreeMap<String, Option<&str>> = BTreeMap::new();
+        for feature in reader.read(file_path)? {
+            info.feature_count += 1;
+            let feature = match feature {
+                Ok(feature) => feature,
+                Err(e) if SkipReason::from_error(&e) == SkipReason::NullGeometry => {
+                    info.null_geometry_count += 1;
+                    continue;
+                }
+                Err(_) => {
+                    info.skipped_count += 1;
+                    continue;
+                }
+            };
+            *info
+                .geometry_types
+                .entry(geometry_type(&feature.geometry).to_string())
+                .or_default() += 1;
+            info.vertex_count += feature.geometry.coords_count();
+            if let Some(rect) = feature.geometry.bounding_rect() {
+                info.bbox = Some(match info.bbox {
+                    Some(bbox) => merge_rects(bbox, rect),
+                    None => rect,
+                });
+            }
+            for (name, value) in &feature.properties {
+                let field_type = field_types.entry(name.clone()).or_insert(None);
+                *field_type = match (*field_type, prop_type(value)) {
+                    (current, None) => current,
+                    (None, value_type) => value_type,
+                    (Some(current), Some(value_type)) if current == value_type => Some(current),
+                    _ => Some("mixed"),
+                };
+            }
+        }
+        info.fields = declared_fields.unwrap_or_else(|| {
+            field_types
+                .into_iter()
+                .map(|(name, t)| (name, t.unwrap_or("null").to_string()))
+                .collect()
+        });
+        Ok(info)
+    }
+}
+
+impl fmt::Display for DatasetInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Format: {}", self.format)?;
+        writeln!(f, "Features: {}", self.feature_count)?;
+        for (geom_type, count) in &self.geometry_types {
+            writeln!(f, "  {}: {}", geom_type, count)?;
+        }
+        writeln!(f, "Null geometries: {}", self.null_geometry_count)?;
+        if self.skipped_count > 0 {
+            writeln!(f, "Unreadable features: {}", self.skipped_count)?;
+        }
+        writeln!(f, "Vertices: {}", self.vertex_count)?;
+        // Inputs that aren't transformed to lon/lat are summarized in their own coordinates
+        let label = match self.is_lon_lat {
+            true => "Bounding box (WGS84)",
+            false => "Bounding box",
+        };
+        match self.bbox {
+            Some(bbox) => writeln!(
+                f,
+                "{}: {},{},{},{}",
+                label,
+                bbox.min().x,
+                bbox.min().y,
+                bbox.max().x,
+                bbox.max().y
+            )?,
+            None => writeln!(f, "{}: none", label)?,
+        }
+        writeln!(f, "CRS: {}", self.crs.as_deref().unwrap_or("none"))?;
+        writeln!(f, "Fields: {}", self.fields.len())?;
+        for (name, field_type) in &self.fields {
+            writeln!(f, "  {}: {}", name, field_type)?;
+        }
+        Ok(())
+    }
+}
+
+/// Get the name of a geometry's type
+fn geometry_type(geom: &Geometry<f64>) -> &'static str {
+    match geom {
+        Geometry::Point(_) => "Point",
+        Geometry::Line(_) => "Line",
+        Geometry::LineString(_) => "LineString",
+        Geometry::Polygon(_) => "Polygon",
+        Geometry::MultiPoint(_) => "MultiPoint",
+        Geometry::MultiLineString(_) => "MultiLineString",
+        Geometry::MultiPolygon(_) => "MultiPolygon",
+        Geometry::GeometryCollection(_) => "GeometryCollection",
+        Geometry::Rect(_) => "Rect",
+        Geometry::Triangle(_) => "Triangle",
+    }
+}
+
+/// Get the type of a property value, or None if it's null so that it doesn't affect the type
+/// inferred for a field
+fn prop_type(value: &PropValue) -> Option<&'static str> {
+    match value {
+        PropValue::Null => None,
+        PropValue::Bool(_) => Some("boolean"),
+        PropValue::Number(_) => Some("number"),
+        PropValue::String(_) => Some("string"),
+    }
+}
+
+/// Get the rectangle covering two rectangles
+fn merge_rects(a: Rect<f64>, b: Rect<f64>) -> Rect<f64> {
+    Rect::new(
+        (a.min().x.min(b.min().x), a.min().y.min(b.min().y)),
+        (a.max().x.max(b.max().x), a.max().y.max(b.max().y)),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::input::{CsvReader, GeoJsonReader, ShapefileReader};
+
+    #[test]
+    fn test_geojson_info() {
+        let info = DatasetInfo::read(&GeoJsonReader, "./fixtures/input.geojson").unwrap();
+        assert_eq!(info.format, "geojson");
+        assert_eq!(info.feature_count, 4);
+        assert_eq!(
+            info.geometry_types.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("LineString".to_string(), 1),
+                ("MultiPolygon".to_string(), 1),
+                ("Point".to_string(), 1),
+                ("Polygon".to_string(), 1)
+            ]
+        );
+        assert_eq!(info.vertex_count, 18);
+        assert_eq!(
+            info.bbox,
+            Some(Rect::new(
+                (-87.71553039550781, 41.86137915587359),
+                (-87.62077331542969, 41.97582726102573)
+            ))
+        );
+        assert_eq!(info.crs, None);
+        assert!(info.is_lon_lat);
+    }
+
+    #[test]
+    fn test_geojson_info_invalid_feature() {
+        let info = DatasetInfo::read(&GeoJsonReader, "./fixtures/input_invalid.geojson").unwrap();
+        assert_eq!(info.feature_count, 2);
+        assert_eq!(info.skipped_count, 1);
+        assert_eq!(info.crs.as_deref(), Some("urn:ogc:def:crs:OGC:1.3:CRS84"));
+    }
+
+    #[test]
+    fn test_shapefile_info() {
+        let info = DatasetInfo::read(&ShapefileReader, "./fixtures/input_utm.shp").unwrap();
+        assert_eq!(info.crs.as_deref(), Some("NAD_1983_UTM_Zone_16N"));
+        assert!(info.is_lon_lat);
+        let info =
+            DatasetInfo::read(&ShapefileReader, "./fixtures/input_unsupported_crs.shp").unwrap();
+        assert!(!info.is_lon_lat);
+        assert!(info.to_string().contains("\nBounding box: 443560.59"));
+        assert!(!info.fields.is_empty());
+    }
+
+    #[test]
+    fn test_csv_info() {
+        let reader = CsvReader {
+            geom_col: Some("geom".to_string()),
+            ..CsvReader::default()
+        };
+        let info = DatasetInfo::read(&reader, "./fixtures/input_geom.csv").unwrap();
+        assert_eq!(info.feature_count, 4);
+        assert_eq!(info.null_geometry_count, 1);
+        assert_eq!(info.geometry_types.get("Point"), Some(&1));
+        assert_eq!(
+            info.fields,
+            vec![
+                ("geom".to_string(), "string".to_string()),
+                ("id".to_string(), "number".to_string())
+            ]
+        );
+    }
+}
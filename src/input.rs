@@ -0,0 +1,1991 @@
+//! Readers for the built-in input formats
+
+use std::convert::{TryFrom, TryInto};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Seek};
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{self, Context, Result};
+use flatgeobuf::{FallibleStreamingIterator, FeatureProperties, FgbFeature, FgbReader};
+use geo::{Geometry, Point, Rect};
+use geojson::{self, GeoJson, JsonObject, JsonValue};
+use geozero::wkb::{Ewkb, GpkgWkb, Wkb};
+use geozero::ToGeo;
+use kml::types::Element;
+use kml::{quick_collection, Kml};
+use polyline::decode_polyline;
+use rusqlite::{Connection, OpenFlags};
+use shapefile::Shape;
+use topojson::{to_geojson, TopoJson};
+use wkt::Wkt;
+
+use crate::crs::{read_prj, read_prj_name};
+use crate::properties::{PropValue, Properties, PropertyCollector};
+use crate::reader::{Feature, FeatureIter, GeoReader, SkipReason};
+
+/// Record separator used to delimit GeoJSON text sequences (RFC 8142)
+const GEOJSON_SEQ_RS: u8 = 0x1e;
+
+/// File code at the start of shapefiles, 9994 as a big-endian integer
+const SHP_MAGIC: &[u8] = &[0x00, 0x00, 0x27, 0x0a];
+
+/// Geometry types that WKT strings start with
+const WKT_TYPES: [&str; 7] = [
+    "POINT",
+    "LINESTRING",
+    "POLYGON",
+    "MULTIPOINT",
+    "MULTILINESTRING",
+    "MULTIPOLYGON",
+    "GEOMETRYCOLLECTION",
+];
+
+/// KML elements that can be at the root of a KML input
+const KML_ROOTS: [&str; 9] = [
+    "kml",
+    "Document",
+    "Folder",
+    "Placemark",
+    "Point",
+    "LineString",
+    "LinearRing",
+    "Polygon",
+    "MultiGeometry",
+];
+
+/// Magic bytes at the start of FlatGeobuf version 3 files, followed by a patch version
+const FGB_MAGIC: &[u8] = b"fgb\x03fgb";
+
+/// Magic bytes at the start of SQLite databases, which GeoPackages are
+const SQLITE_MAGIC: &[u8] = b"SQLite format 3\0";
+
+/// Common latitude column names, checked in order and ignoring case
+const LAT_COLUMNS: [&str; 6] = ["lat", "latitude", "y", "y_coord", "ycoord", "point_y"];
+
+/// Common longitude column names, checked in order and ignoring case
+const LON_COLUMNS: [&str; 8] = [
+    "lon",
+    "lng",
+    "long",
+    "longitude",
+    "x",
+    "x_coord",
+    "xcoord",
+    "point_x",
+];
+
+/// Types of GPX features that can be included in the output
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GpxFeature {
+    /// Tracks as MultiLineStrings of their segments
+    Track,
+    /// Routes as LineStrings
+    Route,
+    /// Waypoints as Points
+    Waypoint,
+}
+
+impl FromStr for GpxFeature {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<GpxFeature> {
+        match s.to_ascii_lowercase().as_ref() {
+            "tracks" => Ok(GpxFeature::Track),
+            "routes" => Ok(GpxFeature::Route),
+            "waypoints" => Ok(GpxFeature::Waypoint),
+            f => Err(anyhow::anyhow!("Invalid GPX feature type supplied: {}", f)),
+        }
+    }
+}
+
+/// Open file path (or stdin) for buffered reading
+pub fn open_input(file_path: &str) -> Result<Box<dyn BufRead>> {
+    match file_path {
+        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
+        _ => Ok(Box::new(BufReader::new(
+            fs::File::open(file_path)
+                .with_context(|| format!("There was an error opening file: {}", file_path))?,
+        ))),
+    }
+}
+
+/// Read file path (or stdin) to string
+pub fn read_input_to_string(file_path: &str) -> Result<String> {
+    let mut input_str = String::new();
+    match file_path {
+        "-" => io::stdin()
+            .read_to_string(&mut input_str)
+            .context("There was an error reading from stdin"),
+        _ => fs::File::open(file_path)
+            .with_context(|| format!("There was an error opening file: {}", file_path))?
+            .read_to_string(&mut input_str)
+            .with_context(|| format!("There was an error reading from file: {}", file_path)),
+    }?;
+    Ok(input_str)
+}
+
+/// Read file path (or stdin) to bytes
+pub fn read_input_to_bytes(file_path: &str) -> Result<Vec<u8>> {
+    let mut input_bytes = vec![];
+    match file_path {
+        "-" => io::stdin()
+            .read_to_end(&mut input_bytes)
+            .context("There was an error reading from stdin"),
+        _ => fs::File::open(file_path)
+            .with_context(|| format!("There was an error opening file: {}", file_path))?
+            .read_to_end(&mut input_bytes)
+            .with_context(|| format!("There was an error reading from file: {}", file_path)),
+    }?;
+    Ok(input_bytes)
+}
+
+/// Skip a UTF-8 byte order mark and leading whitespace in the first bytes of a text input
+fn text_start(header: &[u8]) -> &[u8] {
+    let header = header.strip_prefix(b"\xef\xbb\xbf").unwrap_or(header);
+    let start = header
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(header.len());
+    &header[start..]
+}
+
+/// Get the trimmed lines in the first bytes of a text input, skipping the last line since it
+/// may be cut off unless the header holds the whole input
+fn complete_lines(header: &[u8], is_complete: bool) -> impl Iterator<Item = &str> {
+    let text = std::str::from_utf8(header).unwrap_or_else(|e| {
+        // Multi-byte characters may be cut off at the end of the header
+        std::str::from_utf8(&header[..e.valid_up_to()]).unwrap()
+    });
+    let complete = match text.rfind('\n') {
+        _ if is_complete => text,
+        Some(idx) => &text[..idx],
+        None => "",
+    };
+    complete.lines().map(|l| l.trim())
+}
+
+/// Get the name of the root element of an XML document, skipping declarations, comments and
+/// namespace prefixes
+fn xml_root(header: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(text_start(header));
+    let mut rest = text.as_ref();
+    while let Some(tag) = rest.strip_prefix('<') {
+        if tag.starts_with('?') || tag.starts_with('!') {
+            rest = tag.split_once('>').map_or("", |(_, r)| r).trim_start();
+            continue;
+        }
+        let name = tag
+            .split(|c: char| c.is_whitespace() || c == '>' || c == '/')
+            .next()?;
+        return name.rsplit(':').next().map(String::from);
+    }
+    None
+}
+
+/// Get the geometry type of WKB bytes if they start with a byte order marker followed by a
+/// valid WKB, ISO WKB or EWKB geometry type
+fn wkb_type(wkb: &[u8]) -> Option<u32> {
+    let type_id = match wkb {
+        [0, type_bytes @ ..] if type_bytes.len() >= 4 => {
+            u32::from_be_bytes(type_bytes[..4].try_into().unwrap())
+        }
+        [1, type_bytes @ ..] if type_bytes.len() >= 4 => {
+            u32::from_le_bytes(type_bytes[..4].try_into().unwrap())
+        }
+        _ => return None,
+    };
+    // Ignore EWKB flags, then ISO WKB dimensions which are multiples of 1000
+    let type_id = type_id & 0x1FFF_FFFF;
+    (type_id < 4000 && (1..=7).contains(&(type_id % 1000))).then_some(type_id % 1000)
+}
+
+/// Process top-level GeoJSON items
+pub fn process_geojson(gj: GeoJson) -> Vec<Result<Feature>> {
+    match gj {
+        GeoJson::FeatureCollection(collection) => collection
+            .features
+            .into_iter()
+            .flat_map(|f| process_geojson(GeoJson::Feature(f)))
+            .collect(),
+        GeoJson::Feature(feature) => {
+            let properties = feature
+                .properties
+                .iter()
+                .flatten()
+                .map(|(k, v)| (k.clone(), PropValue::from(v)))
+                .collect();
+            match feature.geometry {
+                Some(geometry) => {
+                    vec![geojson_geometry(geometry.value).map(|geom| Feature::new(geom, properties))]
+                }
+                None => vec![Err(anyhow::Error::new(SkipReason::NullGeometry))],
+            }
+        }
+        GeoJson::Geometry(geometry) => {
+            vec![geojson_geometry(geometry.value).map(|geom| Feature::new(geom, Properties::new()))]
+        }
+    }
+}
+
+/// Check that every position in a GeoJSON geometry has at least two coordinates
+fn valid_positions(value: &geojson::Value) -> bool {
+    let valid = |pos: &geojson::Position| pos.len() >= 2;
+    match value {
+        geojson::Value::Point(pos) => valid(pos),
+        geojson::Value::MultiPoint(line) | geojson::Value::LineString(line) => {
+            line.iter().all(valid)
+        }
+        geojson::Value::MultiLineString(poly) | geojson::Value::Polygon(poly) => {
+            poly.iter().flatten().all(valid)
+        }
+        geojson::Value::MultiPolygon(polys) => polys.iter().flatten().flatten().all(valid),
+        geojson::Value::GeometryCollection(geoms) => {
+            geoms.iter().all(|geom| valid_positions(&geom.value))
+        }
+    }
+}
+
+/// Convert a GeoJSON geometry value to a geometry
+fn geojson_geometry(value: geojson::Value) -> Result<Geometry<f64>> {
+    if !valid_positions(&value) {
+        return Err(anyhow::anyhow!(
+            "GeoJSON geometry has positions with fewer than two coordinates"
+        ));
+    }
+    value
+        .try_into()
+        .context("There was an error converting GeoJSON geometry")
+}
+
+/// Reads GeoJSON features from a file or stdin
+pub struct GeoJsonReader;
+
+impl GeoReader for GeoJsonReader {
+    fn names(&self) -> &[&str] {
+        &["geojson"]
+    }
+
+    fn detect(&self, header: &[u8], _is_complete: bool) -> bool {
+        text_start(header).starts_with(b"{")
+    }
+
+    fn read(&self, file_path: &str) -> Result<FeatureIter<'_>> {
+        process_geojson_stream(open_input(file_path)?)
+    }
+
+    fn is_lon_lat(&self, file_path: &str) -> bool {
+        // Coordinates are WGS84 unless an older crs member names another CRS
+        self.crs(file_path)
+            .ok()
+            .flatten()
+            .is_none_or(|name| is_wgs84_name(&name))
+    }
+
+    fn crs(&self, file_path: &str) -> Result<Option<String>> {
+        // Reading the crs member would consume stdin before features can be read
+        if file_path == "-" {
+            return Ok(None);
+        }
+        // Malformed input is reported when features are read
+        let crs = geojson_crs(open_input(file_path)?).ok().flatten();
+        Ok(crs.as_ref().map(geojson_crs_name))
+    }
+}
+
+/// Process a GeoJSON object, streaming the features of a feature collection
+fn process_geojson_stream<'a, R: BufRead + 'a>(reader: R) -> Result<FeatureIter<'a>> {
+    Ok(Box::new(GeoJsonFeatures {
+        stream: JsonObjectStream::new(reader)?,
+        members: JsonObject::new(),
+        has_features: false,
+        in_features: false,
+        pending: vec![].into_iter(),
+        done: false,
+    }))
+}
+
+/// Read the crs member of a GeoJSON object, skipping features without converting them
+fn geojson_crs<R: BufRead>(reader: R) -> Result<Option<JsonValue>> {
+    let mut stream = JsonObjectStream::new(reader)?;
+    while let Some(key) = stream.next_member()? {
+        match key.as_str() {
+            "crs" => return Ok(Some(stream.read_value()?)),
+            "features" => {
+                stream.start_array()?;
+                while stream.next_element()?.is_some() {}
+            }
+            _ => {
+                stream.read_value()?;
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Reads the members of a top-level JSON object in order, with the elements of an array member
+/// read one at a time so that large feature collections don't have to be held in memory
+struct JsonObjectStream<R> {
+    reader: R,
+    /// Whether the next member or element is the first, which isn't preceded by a comma
+    first: bool,
+}
+
+impl<R: BufRead> JsonObjectStream<R> {
+    /// Start reading an object, skipping a byte order mark
+    fn new(mut reader: R) -> Result<JsonObjectStream<R>> {
+        if reader
+            .fill_buf()
+            .context("There was an error reading GeoJSON")?
+            .starts_with(b"\xef\xbb\xbf")
+        {
+            reader.consume(3);
+        }
+        let mut stream = JsonObjectStream {
+            reader,
+            first: true,
+        };
+        match stream.peek()? {
+            Some(b'{') => stream.reader.consume(1),
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Unable to parse GeoJSON: expected an object"
+                ))
+            }
+        }
+        Ok(stream)
+    }
+
+    /// Skip whitespace and get the next byte without consuming it
+    fn peek(&mut self) -> Result<Option<u8>> {
+        loop {
+            let buf = self
+                .reader
+                .fill_buf()
+                .context("There was an error reading GeoJSON")?;
+            match buf.iter().position(|b| !b.is_ascii_whitespace()) {
+                Some(idx) => {
+                    let byte = buf[idx];
+                    self.reader.consume(idx);
+                    return Ok(Some(byte));
+                }
+                None if buf.is_empty() => return Ok(None),
+                None => {
+                    let len = buf.len();
+                    self.reader.consume(len);
+                }
+            }
+        }
+    }
+
+    /// Consume the next byte, which has to be the expected one
+    fn expect(&mut self, expected: u8) -> Result<()> {
+        match self.peek()? {
+            Some(byte) if byte == expected => {
+                self.reader.consume(1);
+                Ok(())
+            }
+            Some(byte) => Err(anyhow::anyhow!(
+                "Unable to parse GeoJSON: expected '{}', found '{}'",
+                expected as char,
+                byte as char
+            )),
+            None => Err(anyhow::anyhow!(
+                "Unable to parse GeoJSON: unexpected end of input"
+            )),
+        }
+    }
+
+    /// Check whether a container is closed, otherwise consume the comma before the next item
+    fn next_item(&mut self, close: u8) -> Result<bool> {
+        if self.peek()? == Some(close) {
+            self.reader.consume(1);
+            self.first = false;
+            return Ok(false);
+        }
+        if !self.first {
+            self.expect(b',')?;
+        }
+        self.first = false;
+        Ok(true)
+    }
+
+    /// Get the key of the next member, or None at the end of the object
+    fn next_member(&mut self) -> Result<Option<String>> {
+        if !self.next_item(b'}')? {
+            return Ok(None);
+        }
+        let key = match self.read_value()? {
+            JsonValue::String(key) => key,
+            key => {
+                return Err(anyhow::anyhow!(
+                    "Unable to parse GeoJSON: invalid key {}",
+                    key
+                ))
+            }
+        };
+        self.expect(b':')?;
+        Ok(Some(key))
+    }
+
+    /// Start reading the elements of an array member
+    fn start_array(&mut self) -> Result<()> {
+        self.expect(b'[')?;
+        self.first = true;
+        Ok(())
+    }
+
+    /// Read the next element of an array member, or None at the end of the array
+    fn next_element(&mut self) -> Result<Option<JsonValue>> {
+        match self.next_item(b']')? {
+            true => self.read_value().map(Some),
+            false => Ok(None),
+        }
+    }
+
+    /// Read the value of a member or element
+    fn read_value(&mut self) -> Result<JsonValue> {
+        match self.peek()? {
+            // Objects, arrays and strings end with a delimiter, so nothing after them is read
+            Some(b'{' | b'[' | b'"') => serde_json::Deserializer::from_reader(&mut self.reader)
+                .into_iter::<JsonValue>()
+                .next()
+                .context("Unable to parse GeoJSON: unexpected end of input")?
+                .context("Unable to parse GeoJSON"),
+            Some(_) => {
+                // Numbers and literals are read up to the next delimiter without consuming it
+                let mut bytes = vec![];
+                loop {
+                    let buf = self
+                        .reader
+                        .fill_buf()
+                        .context("There was an error reading GeoJSON")?;
+                    let len = buf
+                        .iter()
+                        .position(|b| matches!(b, b',' | b'}' | b']') || b.is_ascii_whitespace())
+                        .unwrap_or(buf.len());
+                    let is_end = buf.is_empty() || len < buf.len();
+                    bytes.extend_from_slice(&buf[..len]);
+                    self.reader.consume(len);
+                    if is_end {
+                        break;
+                    }
+                }
+                serde_json::from_slice(&bytes).context("Unable to parse GeoJSON")
+            }
+            None => Err(anyhow::anyhow!(
+                "Unable to parse GeoJSON: unexpected end of input"
+            )),
+        }
+    }
+}
+
+/// Iterator over the features of a GeoJSON object, which can be a feature collection with
+/// members in any order, a single feature or a geometry
+struct GeoJsonFeatures<R> {
+    stream: JsonObjectStream<R>,
+    /// Members other than features, which make up single features and geometries
+    members: JsonObject,
+    has_features: bool,
+    in_features: bool,
+    pending: std::vec::IntoIter<Result<Feature>>,
+    done: bool,
+}
+
+impl<R: BufRead> GeoJsonFeatures<R> {
+    /// Read up to the next feature, or to the end of the object
+    fn advance(&mut self) -> Result<Vec<Result<Feature>>> {
+        loop {
+            if self.in_features {
+                match self.stream.next_element()? {
+                    Some(value) => {
+                        return Ok(match geojson::Feature::from_json_value(value) {
+                            Ok(feature) => process_geojson(GeoJson::Feature(feature)),
+                            Err(e) => {
+                                vec![Err(e).context("There was an error parsing GeoJSON feature")]
+                            }
+                        })
+                    }
+                    None => self.in_features = false,
+                }
+            }
+            match self.stream.next_member()? {
+                Some(key) if key == "features" => {
+                    self.stream.start_array()?;
+                    self.has_features = true;
+                    self.in_features = true;
+                }
+                Some(key) => {
+                    let value = self.stream.read_value()?;
+                    self.members.insert(key, value);
+                }
+                None => {
+                    self.done = true;
+                    if self.has_features {
+                        return Ok(vec![]);
+                    }
+                    // Single features and geometries can only be read once all members are
+                    let gj = GeoJson::from_json_object(std::mem::take(&mut self.members))
+                        .context("There was an error parsing GeoJSON")?;
+                    return Ok(process_geojson(gj));
+                }
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for GeoJsonFeatures<R> {
+    type Item = Result<Feature>;
+
+    fn next(&mut self) -> Option<Result<Feature>> {
+        loop {
+            if let Some(feature) = self.pending.next() {
+                return Some(feature);
+            }
+            if self.done {
+                return None;
+            }
+            match self.advance() {
+                Ok(features) => self.pending = features.into_iter(),
+                // The rest of the input can't be read after invalid JSON
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+/// Check whether a CRS name refers to WGS84 lon/lat, like EPSG:4326 or OGC CRS84
+fn is_wgs84_name(name: &str) -> bool {
+    let name = name.to_ascii_uppercase();
+    name.ends_with("CRS84") || name.ends_with(":4326")
+}
+
+/// Get the name of a CRS from the crs member in older GeoJSON, which can be named or linked
+fn geojson_crs_name(crs: &JsonValue) -> String {
+    let properties = &crs["properties"];
+    match (properties["name"].as_str(), &properties["code"]) {
+        (Some(name), _) => name.to_string(),
+        (None, JsonValue::Null) => crs.to_string(),
+        (None, code) => format!("{}:{}", crs["type"].as_str().unwrap_or("EPSG"), code),
+    }
+}
+
+/// Process GeoJSON text sequences, either newline-delimited or prefixed with the
+/// RFC 8142 record separator
+fn process_geojson_seq<'a, R: BufRead + 'a>(mut reader: R) -> Result<FeatureIter<'a>> {
+    let is_rs_delimited = reader
+        .fill_buf()
+        .context("There was an error reading GeoJSON sequence")?
+        .first()
+        == Some(&GEOJSON_SEQ_RS);
+    let records: Box<dyn Iterator<Item = io::Result<Vec<u8>>>> = if is_rs_delimited {
+        Box::new(reader.split(GEOJSON_SEQ_RS))
+    } else {
+        Box::new(reader.split(b'\n'))
+    };
+    Ok(Box::new(
+        records
+            .filter(|r| !matches!(r, Ok(r) if r.trim_ascii().is_empty()))
+            .flat_map(|r| {
+                let gj = r
+                    .context("There was an error reading GeoJSON sequence")
+                    .and_then(|r| String::from_utf8(r).context("GeoJSON record is not UTF-8"))
+                    .and_then(|r| {
+                        r.parse::<GeoJson>()
+                            .context("There was an error parsing GeoJSON record")
+                    });
+                match gj {
+                    Ok(gj) => process_geojson(gj),
+                    Err(e) => vec![Err(e)],
+                }
+            }),
+    ))
+}
+
+/// Reads newline-delimited or RFC 8142 GeoJSON text sequences from a file or stdin
+pub struct GeoJsonSeqReader;
+
+impl GeoReader for GeoJsonSeqReader {
+    fn names(&self) -> &[&str] {
+        &["geojsonseq", "geojsonl", "ndjson"]
+    }
+
+    fn detect(&self, header: &[u8], is_complete: bool) -> bool {
+        // Sequences have a JSON object on each line, unlike GeoJSON with a single object
+        let mut lines = complete_lines(header, is_complete).filter(|l| !l.is_empty());
+        header.first() == Some(&GEOJSON_SEQ_RS)
+            || matches!(
+                (lines.next(), lines.next()),
+                (Some(first), Some(second)) if first.starts_with('{') && first.ends_with('}') && second.starts_with('{')
+            )
+    }
+
+    fn read(&self, file_path: &str) -> Result<FeatureIter<'_>> {
+        process_geojson_seq(open_input(file_path)?)
+    }
+
+    fn is_lon_lat(&self, _file_path: &str) -> bool {
+        true
+    }
+}
+
+/// Reads features from every object in a TopoJSON topology
+pub struct TopoJsonReader;
+
+impl GeoReader for TopoJsonReader {
+    fn names(&self) -> &[&str] {
+        &["topojson"]
+    }
+
+    fn detect(&self, header: &[u8], _is_complete: bool) -> bool {
+        text_start(header).starts_with(b"{")
+            && String::from_utf8_lossy(header)
+                .split_whitespace()
+                .collect::<String>()
+                .contains("\"type\":\"Topology\"")
+    }
+
+    fn read(&self, file_path: &str) -> Result<FeatureIter<'_>> {
+        let topo = read_input_to_string(file_path)?
+            .parse::<TopoJson>()
+            .context("Unable to parse TopoJSON")?;
+        match topo {
+            TopoJson::Topology(t) => Ok(Box::new(
+                t.list_names()
+                    .into_iter()
+                    .flat_map(|n| match to_geojson(&t, &n) {
+                        Ok(collection) => process_geojson(GeoJson::FeatureCollection(collection)),
+                        Err(e) => vec![Err(e).with_context(|| {
+                            format!("There was an error converting TopoJSON object {}", n)
+                        })],
+                    })
+                    .collect::<Vec<_>>()
+                    .into_iter(),
+            )),
+            _ => Err(anyhow::anyhow!("TopoJSON input must be a Topology object")),
+        }
+    }
+
+    fn is_lon_lat(&self, _file_path: &str) -> bool {
+        true
+    }
+}
+
+/// Reads CSV records as points from lat and lon columns, or as geometries from a column of
+/// WKT, hex-encoded WKB or GeoJSON. Lat and lon columns are detected if not provided
+#[derive(Debug, Clone, Default)]
+pub struct CsvReader {
+    /// Name of the latitude column
+    pub lat_col: Option<String>,
+    /// Name of the longitude column
+    pub lon_col: Option<String>,
+    /// Name of a geometry column, used instead of lat and lon columns
+    pub geom_col: Option<String>,
+}
+
+impl CsvReader {
+    /// Read features from CSV text in any reader
+    pub fn read_csv<'a, R: Read + 'a>(&'a self, input: R) -> Result<FeatureIter<'a>> {
+        let mut rdr = csv::Reader::from_reader(input);
+        let headers = rdr.headers().context("Unable to load CSV headers")?.clone();
+
+        if let Some(geom_col) = &self.geom_col {
+            let geom_idx = headers
+                .iter()
+                .position(|v| v == geom_col)
+                .with_context(|| format!("Geometry column {} not found", geom_col))?;
+
+            return Ok(Box::new(rdr.into_records().map(move |rec_val| {
+                let rec = rec_val.context("Could not parse CSV record")?;
+                match rec.get(geom_idx).map(|v| v.trim()) {
+                    None | Some("") => Err(anyhow::Error::new(SkipReason::NullGeometry)),
+                    Some(value) => Ok(Feature::new(
+                        parse_geom_str(value).context("Could not parse geometry from record")?,
+                        csv_properties(&headers, &rec),
+                    )),
+                }
+            })));
+        }
+
+        let lat_idx = find_csv_column(&headers, self.lat_col.as_deref(), &LAT_COLUMNS, "Lat")?;
+        let lon_idx = find_csv_column(&headers, self.lon_col.as_deref(), &LON_COLUMNS, "Lon")?;
+
+        let points = rdr
+            .records()
+            .map(|rec_val| {
+                let rec = rec_val.context("Could not parse CSV record")?;
+                let lat_val: f64 = rec
+                    .get(lat_idx)
+                    .unwrap()
+                    .parse()
+                    .context("Could not parse lat value from record")?;
+                let lon_val: f64 = rec
+                    .get(lon_idx)
+                    .unwrap()
+                    .parse()
+                    .context("Could not parse lon value from record")?;
+                Ok((Point::new(lon_val, lat_val), csv_properties(&headers, &rec)))
+            })
+            .collect::<Vec<Result<(Point<f64>, Properties)>>>();
+
+        // If columns were detected and latitude values are out of range while longitude values
+        // aren't, the detected columns are likely in the wrong order. Records that couldn't be
+        // parsed are skipped later and don't affect this
+        let parsed = || points.iter().filter_map(|p| p.as_ref().ok());
+        let is_swapped = self.lat_col.is_none()
+            && self.lon_col.is_none()
+            && parsed().any(|(pt, _)| pt.y().abs() > 90.)
+            && parsed().all(|(pt, _)| pt.y().abs() <= 180. && pt.x().abs() <= 90.);
+
+        Ok(Box::new(points.into_iter().map(move |point| {
+            let (pt, properties) = point?;
+            let pt = if is_swapped {
+                Point::new(pt.y(), pt.x())
+            } else {
+                pt
+            };
+            Ok(Feature::new(Geometry::Point(pt), properties))
+        })))
+    }
+}
+
+impl GeoReader for CsvReader {
+    fn names(&self) -> &[&str] {
+        &["csv"]
+    }
+
+    fn detect(&self, header: &[u8], is_complete: bool) -> bool {
+        // Check for a header and first row with the same number of columns
+        if !std::str::from_utf8(header).is_ok_and(|h| !h.starts_with(['{', '[', '<'])) {
+            return false;
+        }
+        let lines = complete_lines(header, is_complete)
+            .take(2)
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut rdr = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(lines.as_bytes());
+        let lengths = rdr
+            .records()
+            .filter_map(|r| r.ok())
+            .map(|r| r.len())
+            .collect::<Vec<_>>();
+        matches!(lengths[..], [a, b] if a > 1 && a == b)
+    }
+
+    fn read(&self, file_path: &str) -> Result<FeatureIter<'_>> {
+        self.read_csv(open_input(file_path)?)
+    }
+
+    fn is_lon_lat(&self, _file_path: &str) -> bool {
+        // Geometry columns can be in any CRS, unlike lat and lon columns
+        self.geom_col.is_none()
+    }
+}
+
+/// Get the properties of a CSV record keyed by column name
+fn csv_properties(headers: &csv::StringRecord, rec: &csv::StringRecord) -> Properties {
+    headers
+        .iter()
+        .zip(rec.iter())
+        .map(|(k, v)| (k.to_string(), PropValue::from_text(v)))
+        .collect()
+}
+
+/// Get the index of a CSV column by name, or detect it from common column names
+fn find_csv_column(
+    headers: &csv::StringRecord,
+    col: Option<&str>,
+    candidates: &[&str],
+    label: &str,
+) -> Result<usize> {
+    let header_list = headers.iter().collect::<Vec<_>>().join(", ");
+    match col {
+        Some(col) => headers.iter().position(|v| v == col).with_context(|| {
+            format!(
+                "{} column {} not found, available columns: {}",
+                label, col, header_list
+            )
+        }),
+        None => candidates
+            .iter()
+            .find_map(|c| {
+                headers
+                    .iter()
+                    .position(|v| v.trim().eq_ignore_ascii_case(c))
+            })
+            .with_context(|| {
+                format!(
+                    "Could not detect {} column from common names ({}), available columns: {}",
+                    label.to_ascii_lowercase(),
+                    candidates.join(", "),
+                    header_list
+                )
+            }),
+    }
+}
+
+/// Parse a geometry from a string containing GeoJSON, hex-encoded WKB or WKT
+fn parse_geom_str(value: &str) -> Result<Geometry<f64>> {
+    if value.starts_with('{') {
+        let gj: GeoJson = value
+            .parse()
+            .context("There was an error parsing GeoJSON")?;
+        return geojson_value_geometry(gj);
+    }
+    match decode_hex(value) {
+        Ok(wkb) => parse_wkb(&wkb),
+        Err(_) => parse_wkt(value),
+    }
+}
+
+/// Convert a GeoJSON geometry, feature or collection in a CSV column to a single geometry,
+/// checking positions like the GeoJSON readers
+fn geojson_value_geometry(gj: GeoJson) -> Result<Geometry<f64>> {
+    let feature_geometry = |feature: geojson::Feature| match feature.geometry {
+        Some(geometry) => geojson_geometry(geometry.value),
+        None => Err(anyhow::Error::new(SkipReason::NullGeometry)),
+    };
+    match gj {
+        GeoJson::Geometry(geometry) => geojson_geometry(geometry.value),
+        GeoJson::Feature(feature) => feature_geometry(feature),
+        GeoJson::FeatureCollection(collection) => Ok(Geometry::GeometryCollection(
+            collection
+                .features
+                .into_iter()
+                .map(feature_geometry)
+                .collect::<Result<_>>()?,
+        )),
+    }
+}
+
+/// Convert a shape to a geometry, with null and multipatch shapes as skipped features
+fn shape_geometry(shape: Shape) -> Result<Geometry<f64>> {
+    match shape {
+        Shape::NullShape => Err(anyhow::Error::new(SkipReason::NullGeometry)),
+        Shape::Multipatch(_) => Err(anyhow::anyhow!("Multipatch shapes are not supported")
+            .context(SkipReason::UnsupportedType)),
+        shape => Geometry::try_from(shape).map_err(|e| anyhow::anyhow!(e)),
+    }
+}
+
+/// Reads a shapefile along with attributes from its .dbf file, transforming coordinates to
+/// lon/lat if its .prj file has a different CRS
+pub struct ShapefileReader;
+
+impl GeoReader for ShapefileReader {
+    fn names(&self) -> &[&str] {
+        &["shp"]
+    }
+
+    fn detect(&self, header: &[u8], _is_complete: bool) -> bool {
+        header.starts_with(SHP_MAGIC)
+    }
+
+    fn read(&self, file_path: &str) -> Result<FeatureIter<'_>> {
+        if file_path == "-" {
+            return Err(anyhow::anyhow!("Shapefiles cannot be read from stdin"));
+        }
+        // Transform coordinates to lon/lat if the .prj file has a different CRS, or keep the
+        // native coordinates if it isn't supported so that the shapes can still be previewed
+        let crs = read_prj(file_path).unwrap_or_else(|e| {
+            eprintln!(
+                "Warning: drawing {} in its own coordinates. {:#}",
+                file_path, e
+            );
+            None
+        });
+        let to_lon_lat = |geom| match &crs {
+            Some(crs) => crs.geom_to_lon_lat(geom),
+            None => geom,
+        };
+
+        // Read attributes from the DBF file if it's available, otherwise only read shapes
+        let features: Vec<Result<Feature>> = if let Ok(mut rdr) =
+            shapefile::Reader::from_path(file_path)
+        {
+            rdr.iter_shapes_and_records()
+                .map(|s| {
+                    let (s, record) = s.context("There was an error reading shape")?;
+                    let properties = record
+                        .into_iter()
+                        .map(|(k, v)| (k, PropValue::from(v)))
+                        .collect();
+                    Ok(Feature::new(to_lon_lat(shape_geometry(s)?), properties))
+                })
+                .collect()
+        } else {
+            let mut rdr = shapefile::ShapeReader::from_path(file_path)
+                .with_context(|| format!("There was an error opening shapefile {}", file_path))?;
+            rdr.iter_shapes()
+                .map(|s| {
+                    let s = s.context("There was an error reading shape")?;
+                    Ok(Feature::new(
+                        to_lon_lat(shape_geometry(s)?),
+                        Properties::new(),
+                    ))
+                })
+                .collect()
+        };
+        Ok(Box::new(features.into_iter()))
+    }
+
+    fn is_lon_lat(&self, file_path: &str) -> bool {
+        // Shapes are transformed to lon/lat if the .prj file has a supported CRS
+        matches!(read_prj(file_path), Ok(Some(_)))
+    }
+
+    fn crs(&self, file_path: &str) -> Result<Option<String>> {
+        read_prj_name(file_path)
+    }
+
+    fn fields(&self, file_path: &str) -> Result<Option<Vec<(String, String)>>> {
+        let dbf_path = Path::new(file_path).with_extension("dbf");
+        if !dbf_path.exists() {
+            return Ok(None);
+        }
+        let rdr = shapefile::dbase::Reader::from_path(&dbf_path)
+            .with_context(|| format!("There was an error opening {}", dbf_path.display()))?;
+        Ok(Some(
+            rdr.fields()
+                .iter()
+                // The dbase crate includes the record deletion flag as a field
+                .filter(|f| f.name() != "DeletionFlag")
+                .map(|f| (f.name().to_string(), format!("{:?}", f.field_type())))
+                .collect(),
+        ))
+    }
+}
+
+/// Parse a geometry from a WKT string
+fn parse_wkt(input_str: &str) -> Result<Geometry<f64>> {
+    let wkt = Wkt::<f64>::from_str(input_str)
+        .map_err(|_| anyhow::anyhow!("There was an error parsing WKT"))?;
+    wkt.try_into()
+        .map_err(|_| anyhow::anyhow!("There was an error converting WKT"))
+}
+
+/// Reads a single WKT geometry
+pub struct WktReader;
+
+impl GeoReader for WktReader {
+    fn names(&self) -> &[&str] {
+        &["wkt"]
+    }
+
+    fn detect(&self, header: &[u8], _is_complete: bool) -> bool {
+        let text = String::from_utf8_lossy(text_start(header)).to_ascii_uppercase();
+        WKT_TYPES.iter().any(|t| {
+            text.strip_prefix(t).is_some_and(|rest| {
+                let rest = rest.trim_start();
+                rest.starts_with('(') || rest.starts_with("EMPTY") || rest.starts_with(['Z', 'M'])
+            })
+        })
+    }
+
+    fn read(&self, file_path: &str) -> Result<FeatureIter<'_>> {
+        let geom = parse_wkt(&read_input_to_string(file_path)?)?;
+        Ok(Box::new(std::iter::once(Ok(Feature::new(
+            geom,
+            Properties::new(),
+        )))))
+    }
+}
+
+/// Parse WKB bytes, reading them as EWKB if the geometry type has EWKB flags set
+fn parse_wkb(wkb: &[u8]) -> Result<Geometry<f64>> {
+    let type_id = match wkb {
+        [0, type_bytes @ ..] if type_bytes.len() >= 4 => {
+            u32::from_be_bytes(type_bytes[..4].try_into().unwrap())
+        }
+        [_, type_bytes @ ..] if type_bytes.len() >= 4 => {
+            u32::from_le_bytes(type_bytes[..4].try_into().unwrap())
+        }
+        _ => return Err(anyhow::anyhow!("WKB geometry is too short")),
+    };
+    // EWKB uses the high bits of the geometry type for Z, M and SRID flags
+    if type_id & 0xE000_0000 != 0 {
+        Ewkb(wkb).to_geo()
+    } else {
+        Wkb(wkb).to_geo()
+    }
+    .context("There was an error parsing WKB")
+}
+
+/// Decode a hex string (optionally prefixed with `\x` or `0x`) to bytes
+fn decode_hex(hex: &str) -> Result<Vec<u8>> {
+    let hex = hex
+        .trim_start_matches("\\x")
+        .trim_start_matches("0x")
+        .as_bytes();
+    if !hex.len().is_multiple_of(2) {
+        return Err(anyhow::anyhow!(
+            "Hex string has an odd number of characters"
+        ));
+    }
+    hex.chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|p| u8::from_str_radix(p, 16).ok())
+                .with_context(|| format!("Invalid hex value: {}", String::from_utf8_lossy(pair)))
+        })
+        .collect()
+}
+
+/// Reads a single WKB or EWKB geometry
+pub struct WkbReader;
+
+impl GeoReader for WkbReader {
+    fn names(&self) -> &[&str] {
+        &["wkb"]
+    }
+
+    fn detect(&self, header: &[u8], _is_complete: bool) -> bool {
+        wkb_type(header).is_some()
+    }
+
+    fn read(&self, file_path: &str) -> Result<FeatureIter<'_>> {
+        let geom = parse_wkb(&read_input_to_bytes(file_path)?)?;
+        Ok(Box::new(std::iter::once(Ok(Feature::new(
+            geom,
+            Properties::new(),
+        )))))
+    }
+}
+
+/// Process hex-encoded WKB or EWKB geometries, one per line
+fn process_hex_wkb<'a, R: BufRead + 'a>(reader: R) -> FeatureIter<'a> {
+    Box::new(
+        reader
+            .lines()
+            .filter(|l| !matches!(l, Ok(l) if l.trim().is_empty()))
+            .map(|l| {
+                let l = l.context("There was an error reading hex WKB")?;
+                Ok(Feature::new(
+                    parse_wkb(&decode_hex(l.trim())?)?,
+                    Properties::new(),
+                ))
+            }),
+    )
+}
+
+/// Reads hex-encoded WKB or EWKB geometries, one per line
+pub struct HexWkbReader;
+
+impl GeoReader for HexWkbReader {
+    fn names(&self) -> &[&str] {
+        &["hexwkb"]
+    }
+
+    fn detect(&self, header: &[u8], is_complete: bool) -> bool {
+        complete_lines(header, is_complete)
+            .find(|l| !l.is_empty())
+            .and_then(|l| decode_hex(l).ok())
+            .is_some_and(|wkb| wkb_type(&wkb).is_some())
+    }
+
+    fn read(&self, file_path: &str) -> Result<FeatureIter<'_>> {
+        Ok(process_hex_wkb(open_input(file_path)?))
+    }
+}
+
+/// Reads an encoded polyline with the precision (number of decimal places) used to encode it
+#[derive(Debug, Clone, Default)]
+pub struct PolylineReader {
+    /// Number of decimal places, which is required to read
+    pub precision: Option<u32>,
+}
+
+impl GeoReader for PolylineReader {
+    fn names(&self) -> &[&str] {
+        &["polyline"]
+    }
+
+    fn read(&self, file_path: &str) -> Result<FeatureIter<'_>> {
+        let precision = self
+            .precision
+            .context("Precision has to be defined for polyline format")?;
+        let lines = decode_polyline(read_input_to_string(file_path)?.trim(), precision)
+            .map_err(|e| anyhow::anyhow!("There was an error decoding polyline: {}", e))?;
+        Ok(Box::new(std::iter::once(Ok(Feature::new(
+            Geometry::LineString(lines),
+            Properties::new(),
+        )))))
+    }
+
+    fn is_lon_lat(&self, _file_path: &str) -> bool {
+        true
+    }
+}
+
+/// Reads KML geometries, with names, descriptions and extended data of placemarks as properties
+pub struct KmlReader;
+
+impl GeoReader for KmlReader {
+    fn names(&self) -> &[&str] {
+        &["kml"]
+    }
+
+    fn detect(&self, header: &[u8], _is_complete: bool) -> bool {
+        // KML elements can be read without a kml root
+        xml_root(header).is_some_and(|root| KML_ROOTS.contains(&root.as_str()))
+    }
+
+    fn read(&self, file_path: &str) -> Result<FeatureIter<'_>> {
+        let kml: Kml = read_input_to_string(file_path)?
+            .parse()
+            .map_err(|_| anyhow::anyhow!("There was an error parsing KML"))?;
+        let mut features = vec![];
+        process_kml(kml, &mut features);
+        Ok(Box::new(features.into_iter()))
+    }
+
+    fn is_lon_lat(&self, _file_path: &str) -> bool {
+        true
+    }
+}
+
+/// Collect KML geometries, along with properties if they're in placemarks
+fn process_kml(kml: Kml, features: &mut Vec<Result<Feature>>) {
+    match kml {
+        Kml::KmlDocument(doc) => {
+            for element in doc.elements {
+                process_kml(element, features);
+            }
+        }
+        Kml::Document { elements, .. } | Kml::Folder { elements, .. } => {
+            for element in elements {
+                process_kml(element, features);
+            }
+        }
+        Kml::Placemark(placemark) => {
+            let mut properties = kml_extended_data(&placemark.children);
+            if let Some(name) = placemark.name {
+                properties.insert("name".to_string(), PropValue::String(name));
+            }
+            if let Some(description) = placemark.description {
+                properties.insert("description".to_string(), PropValue::String(description));
+            }
+            features.push(match placemark.geometry {
+                Some(geometry) => Geometry::try_from(geometry)
+                    .context("There was an error converting KML geometry")
+                    .map(|geom| Feature::new(geom, properties)),
+                None => Err(anyhow::Error::new(SkipReason::NullGeometry)),
+            });
+        }
+        geom @ (Kml::Point(_)
+        | Kml::LineString(_)
+        | Kml::LinearRing(_)
+        | Kml::Polygon(_)
+        | Kml::MultiGeometry(_)) => features.push(
+            quick_collection(geom)
+                .context("There was an error converting KML geometry")
+                .map(|geoms| Feature::new(Geometry::GeometryCollection(geoms), Properties::new())),
+        ),
+        _ => {}
+    }
+}
+
+/// Get properties from the Data and SchemaData values in a placemark's ExtendedData
+fn kml_extended_data(children: &[Element]) -> Properties {
+    children
+        .iter()
+        .filter(|e| e.name == "ExtendedData")
+        .flat_map(|e| e.children.iter())
+        .flat_map(|e| match e.name.as_str() {
+            "Data" => vec![(
+                e.attrs.get("name"),
+                e.children
+                    .iter()
+                    .find(|c| c.name == "value")
+                    .and_then(|c| c.content.as_ref()),
+            )],
+            "SchemaData" => e
+                .children
+                .iter()
+                .filter(|c| c.name == "SimpleData")
+                .map(|c| (c.attrs.get("name"), c.content.as_ref()))
+                .collect(),
+            _ => vec![],
+        })
+        .filter_map(|(name, value)| {
+            Some((
+                name?.clone(),
+                value.map_or(PropValue::Null, |v| PropValue::from_text(v)),
+            ))
+        })
+        .collect()
+}
+
+/// Convert FlatGeobuf features from an iterator over either a file or stdin
+fn process_fgb_features<I>(mut features: I) -> Result<Vec<Result<Feature>>>
+where
+    I: FallibleStreamingIterator<Item = FgbFeature, Error = flatgeobuf::Error>,
+{
+    let mut fgb_features = vec![];
+    while let Some(feature) = features
+        .next()
+        .context("There was an error reading FlatGeobuf feature")?
+    {
+        if feature.geometry().is_none() {
+            fgb_features.push(Err(anyhow::Error::new(SkipReason::NullGeometry)));
+            continue;
+        }
+        let geom = feature
+            .to_geo()
+            .context("There was an error converting FlatGeobuf geometry");
+        let mut collector = PropertyCollector::default();
+        let properties = feature
+            .process_properties(&mut collector)
+            .map(|_| collector.0)
+            .context("There was an error reading FlatGeobuf properties");
+        fgb_features.push(geom.and_then(|geom| Ok(Feature::new(geom, properties?))));
+    }
+    Ok(fgb_features)
+}
+
+/// Reads FlatGeobuf features from a file or stdin, using the spatial index to only read
+/// features in the bbox if both are available
+#[derive(Debug, Clone, Default)]
+pub struct FlatGeobufReader {
+    /// Extent to read features in
+    pub bbox: Option<Rect<f64>>,
+}
+
+impl GeoReader for FlatGeobufReader {
+    fn names(&self) -> &[&str] {
+        &["fgb"]
+    }
+
+    fn detect(&self, header: &[u8], _is_complete: bool) -> bool {
+        header.starts_with(FGB_MAGIC)
+    }
+
+    fn read(&self, file_path: &str) -> Result<FeatureIter<'_>> {
+        let features = match file_path {
+            "-" => {
+                let fgb = FgbReader::open(BufReader::new(io::stdin()))
+                    .context("There was an error opening FlatGeobuf from stdin")?;
+                // Use the spatial index to skip features outside of the bbox if it's available
+                let features = match self.bbox {
+                    Some(b) if fgb.header().index_node_size() > 0 => {
+                        fgb.select_bbox_seq(b.min().x, b.min().y, b.max().x, b.max().y)
+                    }
+                    _ => fgb.select_all_seq(),
+                }
+                .context("There was an error reading FlatGeobuf")?;
+                process_fgb_features(features)?
+            }
+            _ => {
+                let fgb = open_fgb(file_path)?;
+                let features = match self.bbox {
+                    Some(b) if fgb.header().index_node_size() > 0 => {
+                        fgb.select_bbox(b.min().x, b.min().y, b.max().x, b.max().y)
+                    }
+                    _ => fgb.select_all(),
+                }
+                .with_context(|| format!("There was an error reading FlatGeobuf {}", file_path))?;
+                process_fgb_features(features)?
+            }
+        };
+        Ok(Box::new(features.into_iter()))
+    }
+}
+
+/// Open a FlatGeobuf file for reading with seek support
+fn open_fgb(file_path: &str) -> Result<FgbReader<impl Read + Seek>> {
+    let file = fs::File::open(file_path)
+        .with_context(|| format!("There was an error opening file: {}", file_path))?;
+    FgbReader::open(BufReader::new(file))
+        .with_context(|| format!("There was an error opening FlatGeobuf {}", file_path))
+}
+
+/// Get the table and geometry column names of each feature layer in a GeoPackage
+fn gpkg_layers(conn: &Connection) -> Result<Vec<(String, String)>> {
+    let mut stmt = conn
+        .prepare("SELECT table_name, column_name FROM gpkg_geometry_columns ORDER BY table_name")
+        .context("Unable to load GeoPackage geometry columns")?;
+    let layers = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<Vec<(String, String)>>>()
+        .context("Unable to load GeoPackage geometry columns")?;
+    Ok(layers)
+}
+
+/// Quote a SQLite identifier so that table and column names can be used in queries
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Reads features from a GeoPackage layer, which can be omitted if there is only one
+#[derive(Debug, Clone, Default)]
+pub struct GeoPackageReader {
+    /// Name of the layer's table
+    pub layer: Option<String>,
+}
+
+impl GeoReader for GeoPackageReader {
+    fn names(&self) -> &[&str] {
+        &["gpkg"]
+    }
+
+    fn detect(&self, header: &[u8], _is_complete: bool) -> bool {
+        header.starts_with(SQLITE_MAGIC)
+    }
+
+    fn read(&self, file_path: &str) -> Result<FeatureIter<'_>> {
+        if file_path == "-" {
+            return Err(anyhow::anyhow!(
+                "GeoPackage files cannot be read from stdin"
+            ));
+        }
+        let conn = Connection::open_with_flags(file_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .with_context(|| format!("There was an error opening GeoPackage {}", file_path))?;
+
+        let layers = gpkg_layers(&conn)?;
+        let layer_names = layers
+            .iter()
+            .map(|(table, _)| table.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let (table, geom_col) = match (&self.layer, &layers[..]) {
+            (Some(name), _) => layers
+                .iter()
+                .find(|(table, _)| table == name)
+                .with_context(|| {
+                    format!(
+                        "Layer {} not found, available layers: {}",
+                        name, layer_names
+                    )
+                })?,
+            (None, [layer]) => layer,
+            (None, []) => return Err(anyhow::anyhow!("No feature layers found in GeoPackage")),
+            (None, _) => {
+                return Err(anyhow::anyhow!(
+                    "GeoPackage has multiple layers, select one with --layer: {}",
+                    layer_names
+                ))
+            }
+        };
+
+        let mut stmt = conn
+            .prepare(&format!("SELECT * FROM {}", quote_identifier(table)))
+            .with_context(|| format!("Unable to query GeoPackage layer {}", table))?;
+        let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+        let geom_idx = columns
+            .iter()
+            .position(|c| c == geom_col)
+            .with_context(|| format!("Geometry column {} not found", geom_col))?;
+
+        let mut rows = stmt.query([])?;
+        let mut features = vec![];
+        while let Some(row) = rows
+            .next()
+            .context("There was an error reading GeoPackage feature")?
+        {
+            let blob: rusqlite::Result<Option<Vec<u8>>> = row.get(geom_idx);
+            let geom = match blob.context("There was an error reading GeoPackage geometry") {
+                Ok(Some(b)) => GpkgWkb(b)
+                    .to_geo()
+                    .context("There was an error parsing GeoPackage geometry"),
+                Ok(None) => Err(anyhow::Error::new(SkipReason::NullGeometry)),
+                Err(e) => Err(e),
+            };
+            let properties = columns
+                .iter()
+                .enumerate()
+                .filter(|(idx, _)| *idx != geom_idx)
+                .map(|(idx, col)| Ok((col.clone(), PropValue::from(row.get_ref(idx)?))))
+                .collect::<rusqlite::Result<Properties>>()
+                .context("There was an error reading GeoPackage properties");
+            features.push(geom.and_then(|geom| Ok(Feature::new(geom, properties?))));
+        }
+        Ok(Box::new(features.into_iter()))
+    }
+}
+
+/// Reads the selected types of GPX features with their names as properties
+#[derive(Debug, Clone)]
+pub struct GpxReader {
+    /// Types of features to read
+    pub features: Vec<GpxFeature>,
+}
+
+impl Default for GpxReader {
+    fn default() -> GpxReader {
+        GpxReader {
+            features: vec![GpxFeature::Track, GpxFeature::Route, GpxFeature::Waypoint],
+        }
+    }
+}
+
+impl GeoReader for GpxReader {
+    fn names(&self) -> &[&str] {
+        &["gpx"]
+    }
+
+    fn detect(&self, header: &[u8], _is_complete: bool) -> bool {
+        xml_root(header).is_some_and(|root| root == "gpx")
+    }
+
+    fn read(&self, file_path: &str) -> Result<FeatureIter<'_>> {
+        let gpx = gpx::read(open_input(file_path)?).context("There was an error parsing GPX")?;
+
+        let mut geoms: Vec<(Geometry<f64>, &Option<String>)> = vec![];
+        if self.features.contains(&GpxFeature::Track) {
+            geoms.extend(
+                gpx.tracks
+                    .iter()
+                    .map(|t| (Geometry::MultiLineString(t.multilinestring()), &t.name)),
+            );
+        }
+        if self.features.contains(&GpxFeature::Route) {
+            geoms.extend(
+                gpx.routes
+                    .iter()
+                    .map(|r| (Geometry::LineString(r.linestring()), &r.name)),
+            );
+        }
+        if self.features.contains(&GpxFeature::Waypoint) {
+            geoms.extend(
+                gpx.waypoints
+                    .iter()
+                    .map(|w| (Geometry::Point(w.point()), &w.name)),
+            );
+        }
+
+        let features: Vec<Feature> = geoms
+            .into_iter()
+            .map(|(geom, name)| {
+                let properties = name
+                    .iter()
+                    .map(|n| ("name".to_string(), PropValue::String(n.clone())))
+                    .collect();
+                Feature::new(geom, properties)
+            })
+            .collect();
+        Ok(Box::new(features.into_iter().map(Ok)))
+    }
+
+    fn is_lon_lat(&self, _file_path: &str) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::map_grid::{GridGeom, Rings};
+    use float_cmp::approx_eq;
+    use geo_types::Line;
+
+    fn read_features(reader: &dyn GeoReader, file_path: &str) -> Vec<Feature> {
+        collect_features(reader.read(file_path).unwrap())
+    }
+
+    fn collect_features(features: FeatureIter) -> Vec<Feature> {
+        features
+            .filter(
+                |f| !matches!(f, Err(e) if SkipReason::from_error(e) == SkipReason::NullGeometry),
+            )
+            .collect::<Result<_>>()
+            .unwrap()
+    }
+
+    fn grid_geoms(features: Vec<Feature>, is_area: bool) -> Vec<GridGeom<f64>> {
+        features
+            .into_iter()
+            .flat_map(|f| GridGeom::vec_from_geom(f.geometry, 0., is_area, Rings::All))
+            .collect()
+    }
+
+    fn csv_reader(
+        lat_col: Option<&str>,
+        lon_col: Option<&str>,
+        geom_col: Option<&str>,
+    ) -> CsvReader {
+        CsvReader {
+            lat_col: lat_col.map(String::from),
+            lon_col: lon_col.map(String::from),
+            geom_col: geom_col.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_geojson_reader() {
+        let features = read_features(&GeoJsonReader, "./fixtures/input.geojson");
+        let outlines = grid_geoms(features.clone(), false);
+        let lines = outlines.iter().filter(|g| matches!(g, GridGeom::Line(_)));
+        let areas = grid_geoms(features, true);
+        let poly = areas.iter().filter(|g| matches!(g, GridGeom::Polygon(_)));
+        assert_eq!(outlines.len(), 14);
+        assert_eq!(lines.count(), 13);
+        assert_eq!(areas.len(), 5);
+        assert_eq!(poly.count(), 3);
+    }
+
+    #[test]
+    fn test_geojson_reader_members() {
+        let read =
+            |input: &str| collect_features(process_geojson_stream(input.as_bytes()).unwrap());
+        assert!(read(r#"{"type": "FeatureCollection", "features": []}"#).is_empty());
+        let features = read(
+            r#"{"type": "FeatureCollection", "features": [
+                {"type": "Feature", "properties": {"id": 1}, "geometry": {"type": "Point", "coordinates": [1, 2]}},
+                {"type": "Feature", "properties": {"id": 2}, "geometry": null}
+            ], "name": "points", "count": 2, "bbox": [1, 2, 1, 2],
+            "crs": {"type": "name", "properties": {"name": "EPSG:4326"}}}"#,
+        );
+        assert_eq!(features.len(), 1);
+        assert_eq!(features[0].properties["id"], PropValue::Number(1.));
+        assert_eq!(
+            read(r#"{"coordinates": [1, 2], "type": "Point"}"#),
+            vec![Feature::new(
+                Geometry::Point(Point::new(1., 2.)),
+                Properties::new()
+            )]
+        );
+        assert!(process_geojson_stream("[]".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_geojson_crs() {
+        let crs = geojson_crs(
+            r#"{"type": "FeatureCollection", "features": [
+                {"type": "Feature", "properties": {}, "geometry": {"type": "Pointy", "coordinates": [1, 2]}}
+            ], "crs": {"type": "name", "properties": {"name": "EPSG:3857"}}}"#
+                .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(
+            crs.as_ref().map(geojson_crs_name).as_deref(),
+            Some("EPSG:3857")
+        );
+    }
+
+    #[test]
+    fn test_geojson_crs_name() {
+        let named: JsonValue = r#"{"type": "name", "properties": {"name": "EPSG:3857"}}"#
+            .parse()
+            .unwrap();
+        assert_eq!(geojson_crs_name(&named), "EPSG:3857");
+        let code: JsonValue = r#"{"type": "EPSG", "properties": {"code": 4326}}"#.parse().unwrap();
+        assert_eq!(geojson_crs_name(&code), "EPSG:4326");
+        assert!(is_wgs84_name("urn:ogc:def:crs:OGC:1.3:CRS84"));
+        assert!(is_wgs84_name("urn:ogc:def:crs:EPSG::4326"));
+        assert!(!is_wgs84_name("EPSG:3857"));
+    }
+
+    #[test]
+    fn test_process_geojson_invalid() {
+        let gj: GeoJson = r#"{"type": "GeometryCollection", "geometries": [
+            {"type": "Point", "coordinates": [1, 2]},
+            {"type": "LineString", "coordinates": [[1, 2], [3]]}
+        ]}"#
+        .parse()
+        .unwrap();
+        assert!(process_geojson(gj)[0].is_err());
+    }
+
+    #[test]
+    fn test_geojson_seq_reader() {
+        let features = read_features(&GeoJsonSeqReader, "./fixtures/input.geojsonl");
+        let outlines = grid_geoms(features.clone(), false);
+        let lines = outlines.iter().filter(|g| matches!(g, GridGeom::Line(_)));
+        let areas = grid_geoms(features, true);
+        let poly = areas.iter().filter(|g| matches!(g, GridGeom::Polygon(_)));
+        assert_eq!(outlines.len(), 6);
+        assert_eq!(lines.count(), 5);
+        assert_eq!(areas.len(), 3);
+        assert_eq!(poly.count(), 1);
+
+        let rs_delimited = "\x1e{\"type\":\"Point\",\"coordinates\":[1.0,2.0]}\n\x1e{\"type\":\"Feature\",\"properties\":{},\"geometry\":{\"type\":\"Point\",\"coordinates\":[3.0,4.0]}}\n";
+        assert_eq!(
+            grid_geoms(
+                collect_features(process_geojson_seq(rs_delimited.as_bytes()).unwrap()),
+                false
+            ),
+            vec![
+                GridGeom::Point(Point::new(1., 2.)),
+                GridGeom::Point(Point::new(3., 4.))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_topojson_reader() {
+        let features = read_features(&TopoJsonReader, "./fixtures/input.topojson");
+        let outlines = grid_geoms(features.clone(), false);
+        let lines = outlines.iter().filter(|g| matches!(g, GridGeom::Line(_)));
+        let areas = grid_geoms(features, true);
+        let poly = areas.iter().filter(|g| matches!(g, GridGeom::Polygon(_)));
+        assert_eq!(outlines.len(), 14);
+        assert_eq!(lines.count(), 13);
+        assert_eq!(areas.len(), 5);
+        assert_eq!(poly.count(), 3);
+    }
+
+    #[test]
+    fn test_csv_reader() {
+        let reader = csv_reader(Some("one"), Some("two"), None);
+        assert_eq!(
+            grid_geoms(read_features(&reader, "./fixtures/input.csv"), false),
+            vec![
+                GridGeom::Point(Point::<f64>::new(-1.0, 1.0)),
+                GridGeom::Point(Point::<f64>::new(-2.0, 2.0))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_csv_reader_detect_columns() {
+        let reader = CsvReader::default();
+        let input_str = "ID,Latitude,LNG\n1,41.8,-87.6\n";
+        assert_eq!(
+            grid_geoms(
+                collect_features(reader.read_csv(input_str.as_bytes()).unwrap()),
+                false
+            ),
+            vec![GridGeom::Point(Point::<f64>::new(-87.6, 41.8))]
+        );
+        let input_str = "X_COORD,y\n41.8,-118.2\n34.1,-118.3\n";
+        assert_eq!(
+            grid_geoms(
+                collect_features(reader.read_csv(input_str.as_bytes()).unwrap()),
+                false
+            ),
+            vec![
+                GridGeom::Point(Point::<f64>::new(-118.2, 41.8)),
+                GridGeom::Point(Point::<f64>::new(-118.3, 34.1))
+            ]
+        );
+        let err = reader
+            .read("./fixtures/input.csv")
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("available columns: one, two"));
+        let err = csv_reader(Some("one"), Some("lon"), None)
+            .read("./fixtures/input.csv")
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.starts_with("Lon column lon not found"));
+    }
+
+    #[test]
+    fn test_csv_reader_invalid_record() {
+        let reader = CsvReader::default();
+        let input_str = "x,y\n41.8,-118.2\nn/a,-118.3\n34.1,-118.3\n";
+        let features = reader
+            .read_csv(input_str.as_bytes())
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(features.len(), 3);
+        assert!(features[1].is_err());
+        assert_eq!(
+            grid_geoms(features.into_iter().filter_map(|f| f.ok()).collect(), false),
+            vec![
+                GridGeom::Point(Point::<f64>::new(-118.2, 41.8)),
+                GridGeom::Point(Point::<f64>::new(-118.3, 34.1))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_csv_reader_geom_col() {
+        let file_path = "./fixtures/input_geom.csv";
+        let features = read_features(&csv_reader(None, None, Some("geom")), file_path);
+        let outlines = grid_geoms(features.clone(), false);
+        assert_eq!(
+            outlines[..2],
+            vec![
+                GridGeom::Line(Line::<f64>::new((4.0, 6.0), (7.0, 10.0))),
+                GridGeom::Point(Point::<f64>::new(1.0, 2.0)),
+            ]
+        );
+        assert_eq!(outlines.len(), 5);
+        let areas = grid_geoms(features, true);
+        assert!(matches!(areas[..], [_, _, GridGeom::Polygon(_)]));
+        assert!(csv_reader(None, None, Some("wkt")).read(file_path).is_err());
+        let skipped = csv_reader(None, None, Some("geom"))
+            .read(file_path)
+            .unwrap()
+            .filter_map(|f| f.err())
+            .map(|e| SkipReason::from_error(&e))
+            .collect::<Vec<_>>();
+        assert_eq!(skipped, vec![SkipReason::NullGeometry]);
+        // Positions with a single coordinate are skipped instead of panicking
+        let input_str = "id,geom\n1,\"{\"\"type\"\":\"\"Point\"\",\"\"coordinates\"\":[1]}\"\n";
+        let features = csv_reader(None, None, Some("geom"))
+            .read_csv(input_str.as_bytes())
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert!(matches!(features[..], [Err(_)]));
+    }
+
+    #[test]
+    fn test_shapefile_reader() {
+        let points = grid_geoms(
+            read_features(&ShapefileReader, "./fixtures/input_point.shp"),
+            false,
+        );
+        let points = points.iter().filter(|g| matches!(g, GridGeom::Point(_)));
+        let lines = grid_geoms(
+            read_features(&ShapefileReader, "./fixtures/input_line.shp"),
+            false,
+        );
+        let lines = lines.iter().filter(|g| matches!(g, GridGeom::Line(_)));
+        let areas = grid_geoms(
+            read_features(&ShapefileReader, "./fixtures/input_area.shp"),
+            true,
+        );
+        let poly = areas.iter().filter(|g| matches!(g, GridGeom::Polygon(_)));
+        assert_eq!(points.count(), 1);
+        assert_eq!(lines.count(), 1);
+        assert_eq!(poly.count(), 3);
+    }
+
+    #[test]
+    fn test_shapefile_reader_prj() {
+        let lines = grid_geoms(
+            read_features(&ShapefileReader, "./fixtures/input_utm.shp"),
+            false,
+        );
+        assert_eq!(lines.len(), 2);
+        match lines[0] {
+            GridGeom::Line(line) => {
+                assert!(approx_eq!(f64, line.start.x, -87.68, epsilon = 1e-6));
+                assert!(approx_eq!(f64, line.start.y, 41.86, epsilon = 1e-6));
+                assert!(approx_eq!(f64, line.end.x, -87.63, epsilon = 1e-6));
+                assert!(approx_eq!(f64, line.end.y, 41.88, epsilon = 1e-6));
+            }
+            _ => panic!("Expected a line"),
+        }
+    }
+
+    #[test]
+    fn test_shapefile_reader_unsupported_prj() {
+        // Shapes are kept in the shapefile's coordinates if the CRS isn't supported
+        let features = read_features(&ShapefileReader, "./fixtures/input_unsupported_crs.shp");
+        let mut rdr = shapefile::ShapeReader::from_path("./fixtures/input_utm.shp").unwrap();
+        let shapes = rdr
+            .iter_shapes()
+            .map(|s| shape_geometry(s.unwrap()).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            features.into_iter().map(|f| f.geometry).collect::<Vec<_>>(),
+            shapes
+        );
+    }
+
+    #[test]
+    fn test_wkt_reader() {
+        assert_eq!(
+            grid_geoms(read_features(&WktReader, "./fixtures/input.wkt"), false),
+            vec![
+                GridGeom::Point(Point::<f64>::new(4.0, 6.0)),
+                GridGeom::Line(Line::<f64>::new((4.0, 6.0), (7.0, 10.0))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wkb_reader() {
+        assert_eq!(
+            grid_geoms(read_features(&WkbReader, "./fixtures/input.wkb"), false),
+            vec![GridGeom::Line(Line::<f64>::new((4.0, 6.0), (7.0, 10.0)))]
+        );
+    }
+
+    #[test]
+    fn test_hex_wkb_reader() {
+        assert_eq!(
+            grid_geoms(
+                read_features(&HexWkbReader, "./fixtures/input.hexwkb"),
+                false
+            ),
+            vec![
+                GridGeom::Point(Point::<f64>::new(1.0, 2.0)),
+                GridGeom::Line(Line::<f64>::new((4.0, 6.0), (7.0, 10.0))),
+            ]
+        );
+        let input_str = "\\x010100000000000000000008400000000000001040";
+        assert_eq!(
+            grid_geoms(
+                collect_features(process_hex_wkb(input_str.as_bytes())),
+                false
+            ),
+            vec![GridGeom::Point(Point::<f64>::new(3.0, 4.0))]
+        );
+        assert!(process_hex_wkb("0101zz".as_bytes())
+            .collect::<Result<Vec<_>>>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_polyline_reader() {
+        let reader = PolylineReader { precision: Some(5) };
+        assert_eq!(
+            grid_geoms(
+                read_features(&reader, "./fixtures/input.polyline.txt"),
+                false
+            ),
+            vec![
+                GridGeom::Line(Line::new((-120.2, 38.5), (-120.95, 40.7))),
+                GridGeom::Line(Line::new((-120.95, 40.7), (-126.453, 43.252)))
+            ]
+        );
+        assert!(PolylineReader::default()
+            .read("./fixtures/input.polyline.txt")
+            .is_err());
+    }
+
+    #[test]
+    fn test_kml_reader() {
+        assert_eq!(
+            grid_geoms(read_features(&KmlReader, "./fixtures/input.kml"), false),
+            vec![
+                GridGeom::Line(Line::new((-1., 2.), (-1.5, 3.))),
+                GridGeom::Line(Line::new((-1.5, 3.), (-1.5, 2.))),
+                GridGeom::Line(Line::new((-1.5, 2.), (-1., 2.)))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_kml_reader_placemarks() {
+        let features = read_features(&KmlReader, "./fixtures/input_placemarks.kml");
+        assert_eq!(features.len(), 2);
+        assert_eq!(
+            features[0].properties,
+            Properties::from([
+                ("name".to_string(), PropValue::String("Loop".to_string())),
+                (
+                    "description".to_string(),
+                    PropValue::String("Downtown".to_string())
+                ),
+                ("riders".to_string(), PropValue::Number(120.)),
+            ])
+        );
+        assert_eq!(
+            features[1].properties.get("surface"),
+            Some(&PropValue::String("paved".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_feature_properties() {
+        let features = read_features(&GeoJsonSeqReader, "./fixtures/input.geojsonl");
+        let names: Vec<_> = features.iter().map(|f| f.properties.get("name")).collect();
+        assert_eq!(
+            names,
+            vec![
+                Some(&PropValue::String("line".to_string())),
+                Some(&PropValue::String("area".to_string())),
+                None
+            ]
+        );
+
+        let features = read_features(&ShapefileReader, "./fixtures/input_area.shp");
+        assert_eq!(
+            features[0].properties.get("FID"),
+            Some(&PropValue::Number(0.))
+        );
+
+        let reader = GeoPackageReader {
+            layer: Some("areas".to_string()),
+        };
+        let features = read_features(&reader, "./fixtures/input.gpkg");
+        assert_eq!(
+            features[0].properties.get("name"),
+            Some(&PropValue::String("tri".to_string()))
+        );
+        assert!(!features[0].properties.contains_key("shape"));
+    }
+
+    #[test]
+    fn test_flatgeobuf_reader() {
+        let file_path = "./fixtures/input.fgb";
+        let features = read_features(&FlatGeobufReader::default(), file_path);
+        let outlines = grid_geoms(features.clone(), false);
+        let lines = outlines.iter().filter(|g| matches!(g, GridGeom::Line(_)));
+        let areas = grid_geoms(features, true);
+        let poly = areas.iter().filter(|g| matches!(g, GridGeom::Polygon(_)));
+        assert_eq!(outlines.len(), 14);
+        assert_eq!(lines.count(), 13);
+        assert_eq!(areas.len(), 5);
+        assert_eq!(poly.count(), 3);
+
+        let reader = FlatGeobufReader {
+            bbox: Some(Rect::new((-87.72, 41.87), (-87.71, 41.88))),
+        };
+        assert_eq!(
+            grid_geoms(read_features(&reader, file_path), false),
+            vec![GridGeom::Point(Point::new(
+                -87.71553039550781,
+                41.87723019276536
+            ))]
+        );
+        assert!(reader.detect(&read_input_to_bytes(file_path).unwrap(), true));
+    }
+
+    #[test]
+    fn test_geopackage_reader() {
+        let file_path = "./fixtures/input.gpkg";
+        let layer = |name: &str| GeoPackageReader {
+            layer: Some(name.to_string()),
+        };
+        assert_eq!(
+            grid_geoms(read_features(&layer("points"), file_path), false),
+            vec![
+                GridGeom::Point(Point::new(1., 2.)),
+                GridGeom::Point(Point::new(3., 4.))
+            ]
+        );
+        let features = read_features(&layer("areas"), file_path);
+        assert_eq!(grid_geoms(features.clone(), false).len(), 3);
+        assert!(matches!(
+            grid_geoms(features, true)[..],
+            [GridGeom::Polygon(_)]
+        ));
+
+        let err = GeoPackageReader::default().read(file_path).err().unwrap();
+        assert!(err.to_string().contains("areas, points"));
+        assert!(layer("missing").read(file_path).is_err());
+        assert!(layer("points").detect(&read_input_to_bytes(file_path).unwrap(), true));
+    }
+
+    #[test]
+    fn test_gpx_reader() {
+        let file_path = "./fixtures/input.gpx";
+        let all = grid_geoms(read_features(&GpxReader::default(), file_path), false);
+        let lines = all.iter().filter(|g| matches!(g, GridGeom::Line(_)));
+        let points = all.iter().filter(|g| matches!(g, GridGeom::Point(_)));
+        assert_eq!(lines.count(), 3);
+        assert_eq!(points.count(), 2);
+        let reader = GpxReader {
+            features: vec![GpxFeature::Route],
+        };
+        assert_eq!(
+            grid_geoms(read_features(&reader, file_path), false),
+            vec![GridGeom::Line(Line::new((-87.63, 41.88), (-87.64, 41.89)))]
+        );
+        let reader = GpxReader {
+            features: vec![GpxFeature::Waypoint],
+        };
+        assert_eq!(
+            grid_geoms(read_features(&reader, file_path), false),
+            vec![
+                GridGeom::Point(Point::new(-87.63, 41.88)),
+                GridGeom::Point(Point::new(-87.65, 41.9))
+            ]
+        );
+    }
+}
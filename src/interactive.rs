@@ -0,0 +1,122 @@
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use console::{Key, Term};
+use geo::{Coord, Rect};
+
+use echomap::MapGrid;
+
+/// Proportion of the viewport to move when panning
+const PAN_STEP: f64 = 0.25;
+
+/// Factor to scale the viewport by when zooming
+const ZOOM_STEP: f64 = 2.0;
+
+/// Escape sequences to switch to and from the alternate screen buffer
+const ENTER_ALT_SCREEN: &str = "\x1b[?1049h";
+const LEAVE_ALT_SCREEN: &str = "\x1b[?1049l";
+
+const HELP_TEXT: &str = "arrows/hjkl: pan  +/-: zoom  0: reset  q: quit";
+
+/// Move a viewport by a proportion of its width and height
+fn pan(bbox: Rect<f64>, x_step: f64, y_step: f64) -> Rect<f64> {
+    let offset = Coord {
+        x: bbox.width() * x_step,
+        y: bbox.height() * y_step,
+    };
+    Rect::new(bbox.min() + offset, bbox.max() + offset)
+}
+
+/// Scale a viewport around its center, with factors above 1 zooming in
+fn zoom(bbox: Rect<f64>, factor: f64) -> Rect<f64> {
+    let center = bbox.center();
+    let half_size = Coord {
+        x: bbox.width() / factor / 2.,
+        y: bbox.height() / factor / 2.,
+    };
+    Rect::new(center - half_size, center + half_size)
+}
+
+/// Render the grid to fill the terminal, with a status line at the bottom
+fn draw(term: &mut Term, grid: &mut MapGrid<f64>, bbox: Rect<f64>) -> Result<()> {
+    let (term_height, term_width) = term.size();
+    let rows = term_height.saturating_sub(1).max(1);
+    grid.set_viewport(f64::from(term_width), f64::from(rows), bbox);
+
+    let mut output = String::new();
+    for r in 0..i32::from(rows) {
+        output.push_str(&grid.render_row(r));
+        output.push_str("\r\n");
+    }
+    let status = format!(
+        "{:.5},{:.5},{:.5},{:.5}  {}",
+        bbox.min().x,
+        bbox.min().y,
+        bbox.max().x,
+        bbox.max().y,
+        HELP_TEXT
+    );
+    output.push_str(&console::truncate_str(&status, usize::from(term_width), ""));
+
+    term.move_cursor_to(0, 0)?;
+    term.clear_screen()?;
+    term.write_all(output.as_bytes())?;
+    term.flush()?;
+    Ok(())
+}
+
+/// Read keys and re-render the grid until the user quits
+fn run_loop(term: &mut Term, grid: &mut MapGrid<f64>) -> Result<()> {
+    let extent = grid.extent();
+    let mut bbox = grid.viewport();
+    loop {
+        draw(term, grid, bbox)?;
+        bbox = match term.read_key().context("Error reading key")? {
+            Key::ArrowLeft | Key::Char('h') => pan(bbox, -PAN_STEP, 0.),
+            Key::ArrowRight | Key::Char('l') => pan(bbox, PAN_STEP, 0.),
+            Key::ArrowUp | Key::Char('k') => pan(bbox, 0., PAN_STEP),
+            Key::ArrowDown | Key::Char('j') => pan(bbox, 0., -PAN_STEP),
+            Key::Char('+') | Key::Char('=') => zoom(bbox, ZOOM_STEP),
+            Key::Char('-') | Key::Char('_') => zoom(bbox, 1. / ZOOM_STEP),
+            Key::Char('0') => extent,
+            Key::Char('q') | Key::Escape => return Ok(()),
+            _ => bbox,
+        };
+    }
+}
+
+/// Open a full-screen view of the grid that can be panned and zoomed
+pub fn run(mut grid: MapGrid<f64>) -> Result<()> {
+    let mut term = Term::stdout();
+    if !term.is_term() {
+        return Err(anyhow::anyhow!("Interactive mode requires a terminal"));
+    }
+
+    term.write_all(ENTER_ALT_SCREEN.as_bytes())?;
+    term.hide_cursor()?;
+    let result = run_loop(&mut term, &mut grid);
+    // Restore the terminal even if rendering failed
+    term.show_cursor()?;
+    term.write_all(LEAVE_ALT_SCREEN.as_bytes())?;
+    term.flush()?;
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pan() {
+        let bbox = Rect::new((0., 0.), (4., 2.));
+        assert_eq!(pan(bbox, PAN_STEP, 0.), Rect::new((1., 0.), (5., 2.)));
+        assert_eq!(pan(bbox, 0., -PAN_STEP), Rect::new((0., -0.5), (4., 1.5)));
+    }
+
+    #[test]
+    fn test_zoom() {
+        let bbox = Rect::new((0., 0.), (4., 2.));
+        assert_eq!(zoom(bbox, ZOOM_STEP), Rect::new((1., 0.5), (3., 1.5)));
+        assert_eq!(zoom(bbox, 1. / ZOOM_STEP), Rect::new((-2., -1.), (6., 3.)));
+    }
+}
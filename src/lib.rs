@@ -0,0 +1,42 @@
+//! Read map files and render them as text for previewing in the terminal.
+//!
+//! Features are read by a [`GeoReader`] for the input format, simplified into grid geometries,
+//! indexed into [`GridLayer`]s and rendered by a [`MapGrid`]. Readers for other formats can be
+//! added to a [`ReaderRegistry`] alongside the built-in readers in [`input`]:
+//!
+//! ```no_run
+//! use echomap::reader::read_grid_features;
+//! use echomap::{GridLayer, MapGrid, ReaderRegistry, Rings};
+//! use rstar::RTree;
+//!
+//! let readers = ReaderRegistry::default();
+//! let reader = readers.detect("input.geojson", None)?;
+//! let input =
+//!     read_grid_features(reader, "input.geojson", 0.01, false, Rings::All, false, None)?;
+//! let geoms = input.features.into_iter().flat_map(|f| f.geoms).collect();
+//! let grid = MapGrid::from_layers(
+//!     80.,
+//!     24.,
+//!     vec![GridLayer {
+//!         color: None,
+//!         rtree: RTree::bulk_load(geoms),
+//!     }],
+//! );
+//! print!("{}", grid.render());
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+pub mod charset;
+pub mod color;
+pub mod convert;
+pub mod crs;
+pub mod filter;
+pub mod info;
+pub mod input;
+pub mod map_grid;
+pub mod projection;
+pub mod properties;
+pub mod reader;
+
+pub use map_grid::{GridFeature, GridGeom, GridLayer, MapGrid, Rings};
+pub use reader::{Feature, GeoReader, ReaderRegistry, SkipReason};
@@ -1,59 +1,75 @@
-use std::convert::{TryFrom, TryInto};
 use std::fs;
-use std::io::{self, BufReader, Read};
-use std::str::FromStr;
+use std::io::BufWriter;
 use std::time::Duration;
 
 use anyhow::{self, Context, Result};
-use clap::{Arg, ArgAction, Command};
+use clap::{Arg, ArgAction, ArgMatches, Command};
 use console::Term;
-use geo::{Geometry, Point};
-use geojson::{self, FeatureIterator, GeoJson};
+use geo::{Point, Rect};
 use indicatif::ProgressBar;
-use kml::{quick_collection, Kml};
-use polyline::decode_polyline;
-use rstar::RTree;
-use topojson::{to_geojson, TopoJson};
-use wkt::Wkt;
-
-mod map_grid;
-use map_grid::{GridGeom, MapGrid};
-
-#[derive(Debug, PartialEq)]
-enum InputFormat {
-    GeoJson,
-    TopoJson,
-    Csv,
-    Shapefile,
-    Wkt,
-    Polyline,
-    Kml,
+use rstar::{Envelope, RTree, RTreeObject, AABB};
+
+use echomap::charset::Charset;
+use echomap::color::{parse_hex_color, ColorScale};
+use echomap::convert::ConvertFormat;
+use echomap::filter::Filter;
+use echomap::info::DatasetInfo;
+use echomap::input::{
+    CsvReader, FlatGeobufReader, GeoPackageReader, GpxFeature, GpxReader, PolylineReader,
+};
+use echomap::map_grid::{GridFeature, GridLayer, MapGrid, Rings};
+use echomap::projection::{get_parallels, Projection};
+use echomap::properties::PropValue;
+use echomap::reader::{read_features, read_grid_features, skipped_summary, ReaderRegistry};
+
+mod interactive;
+
+/// Get the values of an option for each input, where options can be provided once for all
+/// inputs or once per input
+fn per_input_values<'a>(
+    matches: &'a ArgMatches,
+    id: &str,
+    input_count: usize,
+) -> Result<Vec<Option<&'a String>>> {
+    let values: Vec<&String> = matches
+        .get_many::<String>(id)
+        .map(|v| v.collect())
+        .unwrap_or_default();
+    match values.len() {
+        0 => Ok(vec![None; input_count]),
+        1 => Ok(vec![Some(values[0]); input_count]),
+        n if n == input_count => Ok(values.into_iter().map(Some).collect()),
+        n if input_count == 1 => Err(anyhow::anyhow!("Expected 1 value for {}, found {}", id, n)),
+        n => Err(anyhow::anyhow!(
+            "Expected 1 or {} values for {}, found {}",
+            input_count,
+            id,
+            n
+        )),
+    }
 }
 
-impl FromStr for InputFormat {
-    type Err = anyhow::Error;
-
-    fn from_str(s: &str) -> Result<InputFormat> {
-        match s.to_ascii_lowercase().as_ref() {
-            "geojson" => Ok(InputFormat::GeoJson),
-            "topojson" => Ok(InputFormat::TopoJson),
-            "csv" => Ok(InputFormat::Csv),
-            "shp" => Ok(InputFormat::Shapefile),
-            "wkt" => Ok(InputFormat::Wkt),
-            "polyline" => Ok(InputFormat::Polyline),
-            "kml" => Ok(InputFormat::Kml),
-            f => Err(anyhow::anyhow!("Invalid format supplied: {}", f)),
+/// Parse which inputs to print polygon area for from "all" or comma-separated input numbers
+fn get_area_layers(area: &str, input_count: usize) -> Result<Vec<bool>> {
+    if area == "all" {
+        return Ok(vec![true; input_count]);
+    }
+    let mut area_layers = vec![false; input_count];
+    for layer in area.split(',') {
+        let idx = layer
+            .trim()
+            .parse::<usize>()
+            .with_context(|| format!("Could not parse area input number: {}", layer))?;
+        if idx == 0 || idx > input_count {
+            return Err(anyhow::anyhow!(
+                "Area input number {} must be between 1 and {}",
+                idx,
+                input_count
+            ));
         }
+        area_layers[idx - 1] = true;
     }
-}
-
-/// Get file format from flag or infer from file path
-fn get_file_format(file_path: &str, file_format: Option<String>) -> Result<InputFormat> {
-    let format_str = match file_format {
-        Some(f) => f,
-        None => file_path.split('.').last().unwrap().to_string(),
-    };
-    format_str.parse()
+    Ok(area_layers)
 }
 
 /// Parse simplification value from float or percentage string
@@ -71,196 +87,224 @@ fn get_simplification(simplify: &str) -> Result<f64> {
     }
 }
 
-/// Read file path (or stdin) to string
-fn read_input_to_string(file_path: &str) -> Result<String> {
-    let mut input_str = String::new();
-    match file_path {
-        "-" => io::stdin()
-            .read_to_string(&mut input_str)
-            .context("There was an error reading from stdin"),
-        _ => fs::File::open(file_path)
-            .unwrap()
-            .read_to_string(&mut input_str)
-            .with_context(|| format!("There was an error reading from file: {}", file_path)),
-    }?;
-    Ok(input_str)
-}
-
-/// Process top-level GeoJSON items
-pub fn process_geojson(gj: GeoJson, simplification: f64, is_area: bool) -> Vec<GridGeom<f64>> {
-    match gj {
-        GeoJson::FeatureCollection(collection) => collection
-            .features
-            .into_iter()
-            .filter_map(|feature| feature.geometry)
-            .flat_map(|g| {
-                let geom: Geometry<f64> = g.value.try_into().unwrap();
-                GridGeom::<f64>::vec_from_geom(geom, simplification, is_area)
-            })
-            .collect(),
-        GeoJson::Feature(feature) => {
-            if let Some(geometry) = feature.geometry {
-                let geom: Geometry<f64> = geometry.value.try_into().unwrap();
-                GridGeom::<f64>::vec_from_geom(geom, simplification, is_area)
-            } else {
-                vec![]
-            }
-        }
-        GeoJson::Geometry(geometry) => {
-            let geom: Geometry<f64> = geometry.value.try_into().unwrap();
-            GridGeom::<f64>::vec_from_geom(geom, simplification, is_area)
-        }
+/// Parse bounding box from a comma-separated "minx,miny,maxx,maxy" string
+fn get_bbox(bbox: &str) -> Result<Rect<f64>> {
+    let coords = bbox
+        .split(',')
+        .map(|v| v.trim().parse::<f64>())
+        .collect::<Result<Vec<f64>, _>>()
+        .with_context(|| format!("Could not parse bbox value: {}", bbox))?;
+    match coords[..] {
+        [min_x, min_y, max_x, max_y] => Ok(Rect::new((min_x, min_y), (max_x, max_y))),
+        _ => Err(anyhow::anyhow!(
+            "Bbox must have four values (minx,miny,maxx,maxy): {}",
+            bbox
+        )),
     }
 }
 
-fn handle_geojson(
-    file_path: &str,
-    simplification: f64,
-    is_area: bool,
-) -> Result<Vec<GridGeom<f64>>> {
-    Ok(match file_path {
-        "-" => FeatureIterator::new(BufReader::new(io::stdin()))
-            .filter_map(|f| f.ok())
-            .flat_map(|f| process_geojson(GeoJson::Feature(f), simplification, is_area))
-            .collect(),
-        _ => FeatureIterator::new(BufReader::new(fs::File::open(file_path)?))
-            .filter_map(|f| f.ok())
-            .flat_map(|f| process_geojson(GeoJson::Feature(f), simplification, is_area))
-            .collect(),
-    })
-}
-
-fn handle_topojson(
-    input_str: String,
-    simplification: f64,
-    is_area: bool,
-) -> Result<Vec<GridGeom<f64>>> {
-    let topo = input_str
-        .parse::<TopoJson>()
-        .context("Unable to parse TopoJSON")?;
-    match topo {
-        TopoJson::Topology(t) => Ok(t
-            .list_names()
-            .into_iter()
-            .map(|n| to_geojson(&t, &n))
-            .filter_map(|g| g.ok())
-            .map(GeoJson::FeatureCollection)
-            .flat_map(|g| process_geojson(g, simplification, is_area))
-            .collect()),
-        _ => unimplemented!(),
+/// Parse center point from a comma-separated "lon,lat" string
+fn get_center(center: &str) -> Result<Point<f64>> {
+    let coords = center
+        .split(',')
+        .map(|v| v.trim().parse::<f64>())
+        .collect::<Result<Vec<f64>, _>>()
+        .with_context(|| format!("Could not parse center value: {}", center))?;
+    match coords[..] {
+        [lon, lat] => Ok(Point::new(lon, lat)),
+        _ => Err(anyhow::anyhow!(
+            "Center must have two values (lon,lat): {}",
+            center
+        )),
     }
 }
 
-fn handle_csv(input_str: String, lat_col: &str, lon_col: &str) -> Result<Vec<GridGeom<f64>>> {
-    let mut rdr = csv::Reader::from_reader(input_str.as_bytes());
-    let headers = rdr.headers().context("Unable to load CSV headers")?;
-
-    let lat_idx = headers
-        .iter()
-        .position(|v| v == lat_col)
-        .with_context(|| format!("Lat column {} not found", lat_col))?;
-    let lon_idx = headers
-        .iter()
-        .position(|v| v == lon_col)
-        .with_context(|| format!("Lon column {} not found", lat_col))?;
-
-    rdr.records()
-        .map(|rec_val| {
-            let rec = rec_val.context("Could not parse CSV record")?;
-            let lat_val: f64 = rec
-                .get(lat_idx)
-                .unwrap()
-                .parse()
-                .context("Could not parse lat value from record")?;
-            let lon_val: f64 = rec
-                .get(lon_idx)
-                .unwrap()
-                .parse()
-                .context("Could not parse lon value from record")?;
-            let pt: Point<f64> = Point::new(lon_val, lat_val);
-            Ok(GridGeom::Point(pt))
-        })
-        .collect()
+/// Get a viewport around a center point where zoom 0 spans 360 degrees of longitude like web
+/// map tiles, with the height matching the aspect ratio of the output
+fn get_zoom_viewport(center: Point<f64>, zoom: f64, width: f64, height: f64) -> Rect<f64> {
+    let half_width = 180. / 2_f64.powf(zoom);
+    // Characters are about twice as tall as they are wide
+    let half_height = half_width * 2. * height / width;
+    Rect::new(
+        (center.x() - half_width, center.y() - half_height),
+        (center.x() + half_width, center.y() + half_height),
+    )
 }
 
-fn handle_shp(file_path: &str, simplification: f64, is_area: bool) -> Result<Vec<GridGeom<f64>>> {
-    let mut rdr = shapefile::ShapeReader::from_path(file_path)
-        .with_context(|| format!("There was an error opening shapefile {}", file_path))?;
-    Ok(rdr
-        .iter_shapes()
-        .filter_map(|s| s.ok())
-        .flat_map(|s| match Geometry::<f64>::try_from(s) {
-            Ok(geom) => GridGeom::<f64>::vec_from_geom(geom, simplification, is_area),
-            Err(_) => vec![],
-        })
-        .collect())
+/// Write the grid to an image file, inferring the format from the file extension
+fn write_output(
+    grid: &MapGrid<f64>,
+    file_path: &str,
+    pixel_size: u32,
+    foreground: (u8, u8, u8),
+    background: (u8, u8, u8),
+) -> Result<()> {
+    let contents = match file_path
+        .split('.')
+        .next_back()
+        .unwrap()
+        .to_ascii_lowercase()
+        .as_ref()
+    {
+        "svg" => grid.to_svg().into_bytes(),
+        "png" => grid.to_png(pixel_size, foreground, background)?,
+        ext => return Err(anyhow::anyhow!("Unsupported output format: {}", ext)),
+    };
+    fs::write(file_path, contents)
+        .with_context(|| format!("There was an error writing to file: {}", file_path))
 }
 
-fn handle_wkt(input_str: String, simplification: f64, is_area: bool) -> Result<Vec<GridGeom<f64>>> {
-    let wkt = Wkt::<f64>::from_str(&input_str)
-        .map_err(|_| anyhow::anyhow!("There was an error parsing WKT"))?;
-    let geom: Geometry<f64> = wkt
-        .try_into()
-        .map_err(|_| anyhow::anyhow!("There was an error converting WKT"))?;
-    Ok(GridGeom::<f64>::vec_from_geom(
-        geom,
-        simplification,
-        is_area,
-    ))
+/// Get the built-in readers, configured with the options for their formats
+fn get_readers(matches: &ArgMatches, bbox: Option<Rect<f64>>) -> Result<ReaderRegistry> {
+    let mut readers = ReaderRegistry::default();
+    readers.register(CsvReader {
+        lat_col: matches.get_one::<String>("lat").cloned(),
+        lon_col: matches.get_one::<String>("lon").cloned(),
+        geom_col: matches.get_one::<String>("geom-col").cloned(),
+    });
+    readers.register(PolylineReader {
+        precision: match matches.get_one::<String>("precision") {
+            Some(precision) => Some(precision.parse().with_context(|| {
+                format!("Precision value {} cannot be parsed as a number", precision)
+            })?),
+            None => None,
+        },
+    });
+    readers.register(GpxReader {
+        features: matches
+            .get_many::<String>("gpx-features")
+            .unwrap()
+            .map(|f| f.parse())
+            .collect::<Result<Vec<GpxFeature>>>()?,
+    });
+    readers.register(FlatGeobufReader { bbox });
+    readers.register(GeoPackageReader {
+        layer: matches.get_one::<String>("layer").cloned(),
+    });
+    Ok(readers)
 }
 
-fn handle_polyline(
-    input_str: String,
-    precision: &str,
-    simplification: f64,
-) -> Result<Vec<GridGeom<f64>>> {
-    let precision: u32 = precision
-        .parse()
-        .context("Precision has to be defined for polyline format")?;
-    let lines = decode_polyline(&input_str, precision).unwrap();
-    Ok(GridGeom::vec_from_geom(
-        geo_types::Geometry::LineString(lines),
-        simplification,
-        false,
-    ))
+/// Print a summary of an input instead of a map
+fn print_info(matches: &ArgMatches) -> Result<()> {
+    let input = matches.get_one::<String>("INPUT").unwrap();
+    let readers = get_readers(matches, None)?;
+    let reader = readers.detect(
+        input,
+        matches.get_one::<String>("format").map(|f| f.as_str()),
+    )?;
+    print!("{}", DatasetInfo::read(reader, input)?);
+    Ok(())
 }
 
-fn handle_kml(input_str: String, simplification: f64, is_area: bool) -> Result<Vec<GridGeom<f64>>> {
-    let kml: Kml = input_str
-        .parse()
-        .map_err(|_| anyhow::anyhow!("There was an error parsing KML"))?;
-    Ok(GridGeom::<f64>::vec_from_geom(
-        geo_types::Geometry::GeometryCollection(quick_collection(kml)?),
-        simplification,
-        is_area,
-    ))
+/// Convert an input to another format instead of printing a map
+fn convert(matches: &ArgMatches) -> Result<()> {
+    let input = matches.get_one::<String>("INPUT").unwrap();
+    let output = matches.get_one::<String>("output").unwrap();
+    let format = match (
+        ConvertFormat::from_path(output)?,
+        matches.get_one::<String>("precision"),
+    ) {
+        (ConvertFormat::Polyline { .. }, Some(precision)) => ConvertFormat::Polyline {
+            precision: precision.parse().with_context(|| {
+                format!("Precision value {} cannot be parsed as a number", precision)
+            })?,
+        },
+        (format, _) => format,
+    };
+
+    let readers = get_readers(matches, None)?;
+    let reader = readers.detect(
+        input,
+        matches.get_one::<String>("format").map(|f| f.as_str()),
+    )?;
+    let mut features = vec![];
+    let skipped = read_features(reader, input, matches.get_flag("strict"), |f| {
+        features.push(f)
+    })?;
+
+    let file = fs::File::create(output)
+        .with_context(|| format!("There was an error creating file: {}", output))?;
+    format
+        .write(&features, BufWriter::new(file))
+        .with_context(|| format!("There was an error writing to file: {}", output))?;
+    if !skipped.is_empty() {
+        eprintln!(
+            "{}: converted {} features, skipped {}{}",
+            input,
+            features.len(),
+            skipped.len(),
+            skipped_summary(&skipped)
+        );
+    }
+    Ok(())
 }
 
 fn main() -> Result<()> {
     let matches = Command::new(env!("CARGO_PKG_NAME"))
         .version(env!("CARGO_PKG_VERSION"))
         .about(env!("CARGO_PKG_DESCRIPTION"))
+        .args_conflicts_with_subcommands(true)
+        .subcommand_negates_reqs(true)
+        .subcommand(Command::new("info")
+            .about("Print the format, feature counts, extent, CRS and fields of an input instead of a map")
+            .arg(Arg::new("INPUT")
+                .help("File to summarize or '-' to read stdin")
+                .required(true)
+                .index(1)))
+        .subcommand(Command::new("convert")
+            .about("Convert an input to GeoJSON, GeoJSONSeq, WKT, CSV with a WKT column or an encoded polyline")
+            .arg(Arg::new("INPUT")
+                .help("File to convert or '-' to read stdin")
+                .required(true)
+                .index(1))
+            .arg(Arg::new("output")
+                .short('o')
+                .long("output")
+                .value_name("OUTPUT")
+                .required(true)
+                .help("File to write, with the format inferred from the extension (geojson, geojsonl, wkt, csv, polyline)")))
         .arg(Arg::new("INPUT")
-            .help("File to parse or '-' to read stdin")
+            .help("Files to parse or '-' to read stdin, printed as layers in order")
             .required(true)
+            .num_args(1..)
             .index(1))
         .arg(Arg::new("format")
+            .global(true)
             .short('f')
             .long("format")
             .value_name("FORMAT")
-            .help("Input file format (tries to infer from file extension by default)")
-            .value_parser(["geojson", "topojson", "csv", "shp", "wkt", "polyline", "kml"])
-            .default_value_if("INPUT", "-", Some("geojson")))
+            .help("Input file format, once for all inputs or once per input (tries to infer from file extension or contents by default)")
+            .value_parser(["geojson", "geojsonseq", "topojson", "csv", "shp", "wkt", "polyline", "kml", "gpx", "fgb", "gpkg", "wkb", "hexwkb"])
+            .action(ArgAction::Append))
         .arg(Arg::new("lon")
+            .global(true)
             .long("lon")
             .value_name("LON")
-            .help("Name of longitude column (if format is 'csv')")
-            .default_value("lon"))
+            .help("Name of longitude column (if format is 'csv'). Detected from common names by default"))
         .arg(Arg::new("lat")
+            .global(true)
             .long("lat")
             .value_name("LAT")
-            .help("Name of latitude column (if format is 'csv')")
-            .default_value("lat"))
+            .help("Name of latitude column (if format is 'csv'). Detected from common names by default"))
+        .arg(Arg::new("geom-col")
+            .global(true)
+            .long("geom-col")
+            .value_name("GEOM_COL")
+            .help("Name of geometry column containing WKT, hex-encoded WKB or GeoJSON, used instead of lat and lon (if format is 'csv')"))
+        .arg(Arg::new("layer")
+            .global(true)
+            .long("layer")
+            .value_name("LAYER")
+            .help("Name of layer to read (if format is 'gpkg')"))
+        .arg(Arg::new("gpx-features")
+            .global(true)
+            .long("gpx-features")
+            .value_name("GPX_FEATURES")
+            .help("Comma-separated GPX feature types to include (if format is 'gpx')")
+            .value_parser(["tracks", "routes", "waypoints"])
+            .value_delimiter(',')
+            .action(ArgAction::Append)
+            .default_value("tracks,routes,waypoints"))
         .arg(Arg::new("rows")
             .short('r')
             .long("rows")
@@ -274,19 +318,114 @@ fn main() -> Result<()> {
         .arg(Arg::new("simplify")
             .short('s')
             .long("simplify")
-            .help("Proportion of removable points to remove (0-1 or 0%-100%)")
+            .help("Proportion of removable points to remove (0-1 or 0%-100%), once for all inputs or once per input")
+            .action(ArgAction::Append)
             .default_value("0.01"))
         .arg(Arg::new("precision")
             .long("precision")
+            .global(true)
             .help("Precision value for polyline parsing")
             .required_if_eq("format", "polyline"))
+        .arg(Arg::new("bbox")
+            .long("bbox")
+            .value_name("BBOX")
+            .allow_hyphen_values(true)
+            .conflicts_with("center")
+            .help("Print a viewport (minx,miny,maxx,maxy) instead of the full extent, only including features intersecting it. Uses the spatial index if format is 'fgb'"))
+        .arg(Arg::new("center")
+            .long("center")
+            .value_name("CENTER")
+            .allow_hyphen_values(true)
+            .requires("zoom")
+            .help("Center (lon,lat) of the viewport to print, used with zoom"))
+        .arg(Arg::new("zoom")
+            .long("zoom")
+            .value_name("ZOOM")
+            .requires("center")
+            .help("Zoom level of the viewport to print, where 0 spans 360 degrees and each level halves it"))
         .arg(Arg::new("area")
             .short('a')
             .long("area")
+            .value_name("LAYERS")
+            .num_args(0..=1)
+            .require_equals(true)
+            .default_missing_value("all")
+            .help("Print polygon area instead of boundaries, optionally only for comma-separated input numbers starting at 1"))
+        .arg(Arg::new("rings")
+            .long("rings")
+            .value_name("RINGS")
+            .help("Polygon rings to print as boundaries if not printing area")
+            .value_parser(["exterior", "interior", "all"])
+            .default_value("all"))
+        .arg(Arg::new("charset")
+            .long("charset")
+            .value_name("CHARSET")
+            .help("Characters to print cells with, from most to least detailed: braille (2x4), sextant (2x3), quadrant (2x2), halfblock (1x2) or ascii density")
+            .value_parser(["braille", "sextant", "quadrant", "halfblock", "ascii"])
+            .default_value("braille"))
+        .arg(Arg::new("projection")
+            .long("projection")
+            .value_name("PROJECTION")
+            .help("Projection to print lon/lat coordinates in")
+            .value_parser(["plate-carree", "web-mercator", "equal-earth", "albers"])
+            .default_value("plate-carree"))
+        .arg(Arg::new("parallels")
+            .long("parallels")
+            .value_name("PARALLELS")
+            .allow_hyphen_values(true)
+            .help("Standard parallels (lat1,lat2) if projection is 'albers'. Defaults to 29.5,45.5"))
+        .arg(Arg::new("color-by")
+            .long("color-by")
+            .value_name("PROPERTY")
+            .help("Color features by a property, categorical for text and graduated for numbers"))
+        .arg(Arg::new("color-layers")
+            .long("color-layers")
+            .action(ArgAction::SetTrue)
+            .conflicts_with("color-by")
+            .help("Color each input layer differently"))
+        .arg(Arg::new("output")
+            .short('o')
+            .long("output")
+            .value_name("OUTPUT")
+            .conflicts_with("interactive")
+            .help("Write the map to an image file instead of printing it, with the format inferred from the extension (svg, png)"))
+        .arg(Arg::new("pixel-size")
+            .long("pixel-size")
+            .value_name("PIXEL_SIZE")
+            .help("Size in pixels of each Braille dot if output is 'png'")
+            .default_value("2"))
+        .arg(Arg::new("foreground")
+            .long("foreground")
+            .value_name("COLOR")
+            .help("Hex color of uncolored layers if output is 'png'")
+            .default_value("#000000"))
+        .arg(Arg::new("background")
+            .long("background")
+            .value_name("COLOR")
+            .help("Hex background color if output is 'png'")
+            .default_value("#ffffff"))
+        .arg(Arg::new("where")
+            .long("where")
+            .value_name("EXPRESSION")
+            .help("Only print features with properties matching an expression, like \"population > 10000 AND state = 'IL'\". Supports =, !=, <, <=, >, >=, AND, OR, NOT, LIKE, IN and IS NULL"))
+        .arg(Arg::new("strict")
+            .global(true)
+            .long("strict")
+            .action(ArgAction::SetTrue)
+            .help("Exit with an error on the first feature that can't be read instead of skipping it"))
+        .arg(Arg::new("interactive")
+            .short('i')
+            .long("interactive")
             .action(ArgAction::SetTrue)
-            .help("Print polygon area instead of boundaries"))
+            .help("Open a full-screen view that can be panned with arrow keys or hjkl and zoomed with +/-"))
         .get_matches();
 
+    match matches.subcommand() {
+        Some(("info", info_matches)) => return print_info(info_matches),
+        Some(("convert", convert_matches)) => return convert(convert_matches),
+        _ => {}
+    }
+
     let (term_height, term_width) = Term::stdout().size();
     let height: f64 = match matches.get_one::<String>("rows") {
         Some(ref rows) => rows
@@ -301,171 +440,226 @@ fn main() -> Result<()> {
         None => Ok(f64::from(term_width)),
     }?;
 
-    // Simplification is scaled by the output size
-    let simplify = get_simplification(matches.get_one::<String>("simplify").unwrap())?;
-    let simplification = simplify / (height * width);
+    let rings: Rings = matches.get_one::<String>("rings").unwrap().parse()?;
+    let projection = match (
+        matches
+            .get_one::<String>("projection")
+            .unwrap()
+            .parse::<Projection>()?,
+        matches.get_one::<String>("parallels"),
+    ) {
+        (Projection::Albers { .. }, Some(parallels)) => {
+            Projection::albers(get_parallels(parallels)?)
+        }
+        (_, Some(_)) => {
+            return Err(anyhow::anyhow!(
+                "Parallels can only be used with the albers projection"
+            ))
+        }
+        (projection, None) => projection,
+    };
+    let bbox = match (
+        matches.get_one::<String>("bbox"),
+        matches.get_one::<String>("center"),
+        matches.get_one::<String>("zoom"),
+    ) {
+        (Some(bbox), _, _) => Some(get_bbox(bbox)?),
+        (None, Some(center), Some(zoom)) => Some(get_zoom_viewport(
+            get_center(center)?,
+            zoom.parse()
+                .with_context(|| format!("Zoom value {} cannot be parsed as a number", zoom))?,
+            width,
+            height,
+        )),
+        _ => None,
+    };
 
     let spinner = ProgressBar::new_spinner();
     spinner.set_message("Reading file");
     spinner.enable_steady_tick(Duration::from_millis(100));
     spinner.set_message("Parsing geography");
 
-    let file_format = get_file_format(
-        matches.get_one::<String>("INPUT").unwrap(),
-        matches.get_one::<String>("format").cloned(),
-    )?;
+    let inputs: Vec<&String> = matches.get_many::<String>("INPUT").unwrap().collect();
+    let formats = per_input_values(&matches, "format", inputs.len())?;
+    let simplify_values = per_input_values(&matches, "simplify", inputs.len())?;
+    let area_layers = match matches.get_one::<String>("area") {
+        Some(area) => get_area_layers(area, inputs.len())?,
+        None => vec![false; inputs.len()],
+    };
 
-    let geoms: Vec<GridGeom<f64>> = match file_format {
-        InputFormat::GeoJson => handle_geojson(
-            matches.get_one::<String>("INPUT").unwrap(),
-            simplification,
-            matches.get_flag("area"),
-        ),
-        InputFormat::TopoJson => handle_topojson(
-            read_input_to_string(matches.get_one::<String>("INPUT").unwrap())?,
-            simplification,
-            matches.get_flag("area"),
-        ),
-        InputFormat::Csv => handle_csv(
-            read_input_to_string(matches.get_one::<String>("INPUT").unwrap())?,
-            matches.get_one::<String>("lat").unwrap(),
-            matches.get_one::<String>("lon").unwrap(),
-        ),
-        InputFormat::Shapefile => handle_shp(
-            matches.get_one::<String>("INPUT").unwrap(),
-            simplification,
-            matches.get_flag("area"),
-        ),
-        InputFormat::Wkt => handle_wkt(
-            read_input_to_string(matches.get_one::<String>("INPUT").unwrap())?,
-            simplification,
-            matches.get_flag("area"),
-        ),
-        InputFormat::Polyline => handle_polyline(
-            read_input_to_string(matches.get_one::<String>("INPUT").unwrap())?,
-            matches.get_one::<String>("precision").unwrap(),
-            simplification,
-        ),
-        InputFormat::Kml => handle_kml(
-            read_input_to_string(matches.get_one::<String>("INPUT").unwrap())?,
-            simplification,
-            matches.get_flag("area"),
-        ),
-    }?;
+    let filter = match matches.get_one::<String>("where") {
+        Some(expr) => Some(expr.parse::<Filter>()?),
+        None => None,
+    };
+
+    let readers = get_readers(&matches, bbox)?;
+    let mut input_features = vec![];
+    for (idx, input) in inputs.iter().enumerate() {
+        let reader = readers.detect(input, formats[idx].map(|f| f.as_str()))?;
+        // Simplification is scaled by the output size
+        let simplify = get_simplification(simplify_values[idx].unwrap())?;
+        input_features.push(read_grid_features(
+            reader,
+            input,
+            simplify / (height * width),
+            area_layers[idx],
+            rings,
+            matches.get_flag("strict"),
+            filter.as_ref(),
+        )?);
+    }
+
+    if let Some(bbox) = bbox {
+        let bbox_envelope = AABB::from_corners(bbox.min().x_y().into(), bbox.max().x_y().into());
+        for feature in input_features
+            .iter_mut()
+            .flat_map(|i| i.features.iter_mut())
+        {
+            feature
+                .geoms
+                .retain(|g| g.envelope().intersects(&bbox_envelope));
+        }
+    }
+
+    // Project coordinates before the grid extent and cell sizes are calculated
+    for feature in input_features
+        .iter_mut()
+        .flat_map(|i| i.features.iter_mut())
+    {
+        feature.geoms = feature
+            .geoms
+            .drain(..)
+            .map(|g| projection.project_geom(g))
+            .collect();
+    }
+
+    // Summarize inputs with features that were skipped or are outside of the viewport, or that
+    // have nothing to render so that a blank map isn't left unexplained
+    let summaries: Vec<String> = input_features
+        .iter()
+        .zip(&inputs)
+        .filter(|(read, _)| {
+            !read.skipped.is_empty()
+                || read.rendered_count() == 0
+                || read.rendered_count() < read.features.len()
+        })
+        .map(|(read, input)| read.summary(input))
+        .collect();
 
     // Create a combined LineString for bounds calculation
     spinner.set_message("Indexing geography");
-    let rtree: RTree<GridGeom<f64>> = RTree::bulk_load(geoms);
-    let grid = MapGrid::new(width, height, rtree);
+    let (layers, legend) = if matches.get_flag("color-layers") {
+        // Layers are labeled by position so that an input passed twice gets its own color
+        let labels: Vec<String> = inputs
+            .iter()
+            .enumerate()
+            .map(|(idx, input)| format!("{}: {}", idx + 1, input))
+            .collect();
+        let scale = ColorScale::Categorical(labels.clone());
+        let colors = scale.colors();
+        let layers = input_features
+            .into_iter()
+            .zip(labels)
+            .map(|(read, label)| GridLayer {
+                color: scale
+                    .class_index(&PropValue::String(label))
+                    .map(|idx| colors[idx]),
+                rtree: RTree::bulk_load(read.features.into_iter().flat_map(|f| f.geoms).collect()),
+            })
+            .collect();
+        (layers, Some(("layer", scale)))
+    } else if let Some(prop) = matches.get_one::<String>("color-by") {
+        let features: Vec<GridFeature<f64>> = input_features
+            .into_iter()
+            .flat_map(|i| i.features)
+            .collect();
+        let scale = ColorScale::new(features.iter().filter_map(|f| f.properties.get(prop)));
+        (scale.layers(prop, features), Some((prop.as_str(), scale)))
+    } else {
+        let geoms = input_features
+            .into_iter()
+            .flat_map(|i| i.features)
+            .flat_map(|f| f.geoms)
+            .collect();
+        (
+            vec![GridLayer {
+                color: None,
+                rtree: RTree::bulk_load(geoms),
+            }],
+            None,
+        )
+    };
+    // Geometries are clipped to the viewport if provided, otherwise use the full extent
+    let mut grid = match bbox {
+        Some(bbox) => MapGrid::with_viewport(width, height, layers, projection.project_rect(bbox)),
+        None => MapGrid::from_layers(width, height, layers),
+    };
+    let charset: Charset = matches.get_one::<String>("charset").unwrap().parse()?;
+    grid.set_charset(charset);
     spinner.finish_and_clear();
-    grid.print()
+    if matches.get_flag("interactive") {
+        interactive::run(grid)?;
+    } else if let Some(output) = matches.get_one::<String>("output") {
+        let pixel_size = matches.get_one::<String>("pixel-size").unwrap();
+        write_output(
+            &grid,
+            output,
+            pixel_size.parse().with_context(|| {
+                format!("Pixel size {} cannot be parsed as a number", pixel_size)
+            })?,
+            parse_hex_color(matches.get_one::<String>("foreground").unwrap())?,
+            parse_hex_color(matches.get_one::<String>("background").unwrap())?,
+        )?;
+    } else {
+        grid.print()?;
+        if let Some((title, scale)) = legend {
+            scale.print_legend(title, charset.char(charset.full_value()))?;
+        }
+    }
+
+    for summary in summaries {
+        eprintln!("{}", summary);
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use geo_types::{Line, Point};
-
-    #[test]
-    fn test_get_file_format() {
-        assert!(matches!(
-            get_file_format("test.GEOJSON", None),
-            Ok(InputFormat::GeoJson)
-        ));
-        assert!(matches!(
-            get_file_format("test.geojson", Some("csv".to_string())),
-            Ok(InputFormat::Csv)
-        ));
-    }
-
-    #[test]
-    fn test_handle_geojson() {
-        let file_path = "./fixtures/input.geojson";
-        let outlines = handle_geojson(file_path, 0., false).unwrap();
-        let lines = outlines.iter().filter(|g| matches!(g, GridGeom::Line(_)));
-        let areas = handle_geojson(file_path, 0., true).unwrap();
-        let poly = areas.iter().filter(|g| matches!(g, GridGeom::Polygon(_)));
-        assert_eq!(outlines.len(), 14);
-        assert_eq!(lines.count(), 13);
-        assert_eq!(areas.len(), 5);
-        assert_eq!(poly.count(), 3);
-    }
 
     #[test]
-    fn test_handle_topojson() {
-        let input_str = include_str!("../fixtures/input.topojson").to_string();
-        let outlines = handle_topojson(input_str.clone(), 0., false).unwrap();
-        let lines = outlines.iter().filter(|g| matches!(g, GridGeom::Line(_)));
-        let areas = handle_topojson(input_str, 0., true).unwrap();
-        let poly = areas.iter().filter(|g| matches!(g, GridGeom::Polygon(_)));
-        assert_eq!(outlines.len(), 14);
-        assert_eq!(lines.count(), 13);
-        assert_eq!(areas.len(), 5);
-        assert_eq!(poly.count(), 3);
+    fn test_get_area_layers() {
+        assert_eq!(get_area_layers("all", 2).unwrap(), vec![true, true]);
+        assert_eq!(get_area_layers("2", 3).unwrap(), vec![false, true, false]);
+        assert!(get_area_layers("0", 2).is_err());
+        assert!(get_area_layers("3", 2).is_err());
     }
 
     #[test]
-    fn test_handle_csv() {
-        let input_str = include_str!("../fixtures/input.csv").to_string();
+    fn test_get_bbox() {
         assert_eq!(
-            handle_csv(input_str, "one", "two").unwrap(),
-            vec![
-                GridGeom::Point(Point::<f64>::new(-1.0, 1.0)),
-                GridGeom::Point(Point::<f64>::new(-2.0, 2.0))
-            ]
+            get_bbox("-1,-2.5,3,4").unwrap(),
+            Rect::new((-1., -2.5), (3., 4.))
         );
+        assert!(get_bbox("1,2,3").is_err());
+        assert!(get_bbox("a,b,c,d").is_err());
     }
 
     #[test]
-    fn test_handle_shp() {
-        let file_path = "./fixtures/input_point.shp";
-        let points = handle_shp(file_path, 0., false).unwrap();
-        let points = points.iter().filter(|g| matches!(g, GridGeom::Point(_)));
-        let file_path = "./fixtures/input_line.shp";
-        let lines = handle_shp(file_path, 0., false).unwrap();
-        let lines = lines.iter().filter(|g| matches!(g, GridGeom::Line(_)));
-        let file_path = "./fixtures/input_area.shp";
-        let areas = handle_shp(file_path, 0., true).unwrap();
-        let poly = areas.iter().filter(|g| matches!(g, GridGeom::Polygon(_)));
-        assert_eq!(points.count(), 1);
-        assert_eq!(lines.count(), 1);
-        assert_eq!(poly.count(), 3);
-    }
-
-    #[test]
-    fn test_handle_wkt() {
-        let input_str = include_str!("../fixtures/input.wkt").to_string();
-        assert_eq!(
-            handle_wkt(input_str, 0., false).unwrap(),
-            vec![
-                GridGeom::Point(Point::<f64>::new(4.0, 6.0)),
-                GridGeom::Line(Line::<f64>::new((4.0, 6.0), (7.0, 10.0))),
-            ]
-        );
+    fn test_get_center() {
+        assert_eq!(get_center("-87.6,41.8").unwrap(), Point::new(-87.6, 41.8));
+        assert!(get_center("-87.6").is_err());
     }
 
     #[test]
-    fn test_handle_polyline() {
-        let input_str = include_str!("../fixtures/input.polyline.txt").to_string();
+    fn test_get_zoom_viewport() {
         assert_eq!(
-            handle_polyline(input_str, "5", 0.).unwrap(),
-            vec![
-                GridGeom::Line(Line::new((-120.2, 38.5), (-120.95, 40.7))),
-                GridGeom::Line(Line::new((-120.95, 40.7), (-126.453, 43.252)))
-            ]
+            get_zoom_viewport(Point::new(0., 0.), 0., 80., 20.),
+            Rect::new((-180., -90.), (180., 90.))
         );
-    }
-
-    #[test]
-    fn test_handle_kml() {
-        let input_str = include_str!("../fixtures/input.kml").to_string();
         assert_eq!(
-            handle_kml(input_str, 0., false).unwrap(),
-            vec![
-                GridGeom::Line(Line::new((-1., 2.), (-1.5, 3.))),
-                GridGeom::Line(Line::new((-1.5, 3.), (-1.5, 2.))),
-                GridGeom::Line(Line::new((-1.5, 2.), (-1., 2.)))
-            ]
+            get_zoom_viewport(Point::new(10., 5.), 2., 40., 20.),
+            Rect::new((-35., -40.), (55., 50.))
         );
     }
 }
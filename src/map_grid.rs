@@ -1,25 +1,86 @@
-use std::char;
+//! Grids of cells that geometries are drawn on and printed from
+
 use std::io::{self, Write};
+use std::str::FromStr;
 
 use anyhow::{Context, Result};
+use console::Style;
 use geo::algorithm::bounding_rect::BoundingRect;
 use geo::algorithm::contains::Contains;
 use geo::algorithm::intersects::Intersects;
 use geo::algorithm::simplify_vw::SimplifyVw;
-use geo::{GeoFloat, Geometry, Line, Point, Polygon, Rect};
+use geo::{Coord, GeoFloat, Geometry, Line, LineString, Point, Polygon, Rect};
 use num_traits::FromPrimitive;
-use rstar::{self, RTree, RTreeNum, RTreeObject, AABB};
+use rstar::{self, Envelope, RTree, RTreeNum, RTreeObject, AABB};
+
+use crate::charset::Charset;
+use crate::color::{color256_to_rgb, rgb_to_hex};
+use crate::properties::Properties;
+
+/// Size in SVG pixels of each printed character, which are about twice as tall as they are wide
+const SVG_CELL_SIZE: [f64; 2] = [8., 16.];
+
+/// Radius in SVG pixels of points
+const SVG_POINT_RADIUS: f64 = 2.;
+
+/// Size in Braille dots of each printed character in PNG output
+const PNG_CELL_DOTS: [u32; 2] = [2, 4];
 
-const CELL_ROWS: i32 = 4;
-const CELL_COLS: i32 = 2;
+/// Color of geometries in layers without a color in exported images
+const DEFAULT_EXPORT_COLOR: &str = "#000000";
 
+/// Polygon rings to draw when printing boundaries instead of area
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rings {
+    /// Only exterior rings
+    Exterior,
+    /// Only interior rings, or holes
+    Interior,
+    /// Exterior and interior rings
+    All,
+}
+
+impl FromStr for Rings {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Rings> {
+        match s.to_ascii_lowercase().as_ref() {
+            "exterior" => Ok(Rings::Exterior),
+            "interior" => Ok(Rings::Interior),
+            "all" => Ok(Rings::All),
+            r => Err(anyhow::anyhow!("Invalid rings value supplied: {}", r)),
+        }
+    }
+}
+
+impl Rings {
+    /// Get the line segments of the selected rings of a polygon
+    fn polygon_lines<T>(self, poly: &Polygon<T>) -> Vec<Line<T>>
+    where
+        T: GeoFloat,
+    {
+        let mut lines = vec![];
+        if self != Rings::Interior {
+            lines.extend(poly.exterior().lines());
+        }
+        if self != Rings::Exterior {
+            lines.extend(poly.interiors().iter().flat_map(|ring| ring.lines()));
+        }
+        lines
+    }
+}
+
+/// Point, line segment or polygon indexed in a grid, with other geometries split into these
 #[derive(Debug, Clone, PartialEq)]
 pub enum GridGeom<T>
 where
     T: GeoFloat + RTreeNum + FromPrimitive,
 {
+    /// Single point
     Point(Point<T>),
+    /// Line segment
     Line(Line<T>),
+    /// Polygon filled as area
     Polygon(Polygon<T>),
 }
 
@@ -28,7 +89,12 @@ where
     T: GeoFloat + RTreeNum + FromPrimitive,
 {
     /// Simplify geometries into component pieces for GridGeom
-    pub fn vec_from_geom(geom: Geometry<T>, simplification: T, is_area: bool) -> Vec<GridGeom<T>> {
+    pub fn vec_from_geom(
+        geom: Geometry<T>,
+        simplification: T,
+        is_area: bool,
+        rings: Rings,
+    ) -> Vec<GridGeom<T>> {
         match geom {
             Geometry::Point(s) => vec![GridGeom::Point(s); 1],
             Geometry::MultiPoint(s) => s.into_iter().map(GridGeom::Point).collect(),
@@ -44,13 +110,15 @@ where
                 .flat_map(|ls| ls.lines().collect::<Vec<_>>())
                 .map(GridGeom::Line)
                 .collect(),
+            // Empty polygons have no extent to index
+            Geometry::Polygon(s) if s.exterior().0.is_empty() => vec![],
             Geometry::Polygon(s) => {
                 if is_area {
                     vec![GridGeom::Polygon(s.simplify_vw(&simplification)); 1]
                 } else {
-                    s.simplify_vw(&simplification)
-                        .exterior()
-                        .lines()
+                    rings
+                        .polygon_lines(&s.simplify_vw(&simplification))
+                        .into_iter()
                         .map(GridGeom::Line)
                         .collect()
                 }
@@ -59,12 +127,13 @@ where
                 if is_area {
                     s.simplify_vw(&simplification)
                         .into_iter()
+                        .filter(|p| !p.exterior().0.is_empty())
                         .map(GridGeom::Polygon)
                         .collect()
                 } else {
                     s.simplify_vw(&simplification)
                         .into_iter()
-                        .flat_map(|p| p.exterior().lines().collect::<Vec<_>>())
+                        .flat_map(|p| rings.polygon_lines(&p))
                         .map(GridGeom::Line)
                         .collect()
                 }
@@ -73,12 +142,43 @@ where
             Geometry::Rect(s) => vec![GridGeom::Polygon(s.to_polygon()); 1],
             Geometry::GeometryCollection(s) => s
                 .into_iter()
-                .flat_map(|g| GridGeom::<T>::vec_from_geom(g, simplification, is_area))
+                .flat_map(|g| GridGeom::<T>::vec_from_geom(g, simplification, is_area, rings))
                 .collect(),
         }
     }
 }
 
+/// Grid geometries generated from a single feature along with its properties
+#[derive(Debug, Clone, PartialEq)]
+pub struct GridFeature<T>
+where
+    T: GeoFloat + RTreeNum + FromPrimitive,
+{
+    /// Geometries that the feature was split into
+    pub geoms: Vec<GridGeom<T>>,
+    /// Properties of the feature
+    pub properties: Properties,
+}
+
+impl<T> GridFeature<T>
+where
+    T: GeoFloat + RTreeNum + FromPrimitive,
+{
+    /// Simplify a feature geometry into grid geometries, keeping its properties
+    pub fn from_geom(
+        geom: Geometry<T>,
+        properties: Properties,
+        simplification: T,
+        is_area: bool,
+        rings: Rings,
+    ) -> GridFeature<T> {
+        GridFeature {
+            geoms: GridGeom::vec_from_geom(geom, simplification, is_area, rings),
+            properties,
+        }
+    }
+}
+
 impl<T> RTreeObject for GridGeom<T>
 where
     T: GeoFloat + RTreeNum + FromPrimitive,
@@ -92,35 +192,41 @@ where
                 let bb = line.bounding_rect();
                 AABB::from_corners([bb.min().x, bb.min().y], [bb.max().x, bb.max().y])
             }
-            GridGeom::Polygon(poly) => {
-                let bb = poly.bounding_rect().unwrap();
-                AABB::from_corners([bb.min().x, bb.min().y], [bb.max().x, bb.max().y])
-            }
+            GridGeom::Polygon(poly) => match poly.bounding_rect() {
+                Some(bb) => AABB::from_corners([bb.min().x, bb.min().y], [bb.max().x, bb.max().y]),
+                None => AABB::new_empty(),
+            },
         }
     }
 }
 
-/// Convert row/col coordinates to associated Braille hex value
-pub fn braille_cell_value(row: i32, col: i32) -> u32 {
-    match (row, col) {
-        (0, 0) => 0x01,
-        (0, 1) => 0x08,
-        (1, 0) => 0x02,
-        (1, 1) => 0x10,
-        (2, 0) => 0x04,
-        (2, 1) => 0x20,
-        (3, 0) => 0x40,
-        (3, 1) => 0x80,
-        (_, _) => 0x00,
-    }
+/// Indexed geometries printed with an optional 256-color terminal code
+pub struct GridLayer<T>
+where
+    T: GeoFloat + RTreeNum + FromPrimitive,
+{
+    /// 256-color terminal code, or None for the default color
+    pub color: Option<u8>,
+    /// Spatial index of the layer's geometries
+    pub rtree: RTree<GridGeom<T>>,
 }
 
-/// Add the Braille offset base to the calculated cell value to generate a char
-pub fn braille_char(suffix: u32) -> char {
-    let braille_offset = 0x2800;
-    char::from_u32(braille_offset + suffix).unwrap()
+/// Get the combined envelope of all non-empty layers
+fn layers_extent<T>(layers: &[GridLayer<T>]) -> Rect<T>
+where
+    T: GeoFloat + RTreeNum + FromPrimitive,
+{
+    let envelope = layers
+        .iter()
+        .filter(|layer| layer.rtree.size() > 0)
+        .map(|layer| layer.rtree.root().envelope())
+        .reduce(|a, b| a.merged(&b))
+        .unwrap_or_else(AABB::new_empty);
+    Rect::new(envelope.lower(), envelope.upper())
 }
 
+/// Grid of characters covering a viewport, where each character shows which of its sub-cells
+/// have geometries in them
 pub struct MapGrid<T>
 where
     T: GeoFloat + RTreeNum + FromPrimitive,
@@ -128,18 +234,74 @@ where
     rows: i32,
     cols: i32,
     bbox: Rect<T>,
+    /// Viewport that geometries are clipped to when one is provided, which cells outside of
+    /// are left empty
+    clip: Option<Rect<T>>,
     cell_size: [f64; 2],
     inner_cell_size: [f64; 2],
-    rtree: RTree<GridGeom<T>>,
+    charset: Charset,
+    layers: Vec<GridLayer<T>>,
 }
 
 impl<T> MapGrid<T>
 where
     T: GeoFloat + RTreeNum + FromPrimitive,
 {
+    /// Create a grid from a single uncolored layer
     pub fn new(width: f64, height: f64, rtree: RTree<GridGeom<T>>) -> MapGrid<T> {
-        let envelope = rtree.root().envelope();
-        let bbox = Rect::new(envelope.lower(), envelope.upper());
+        MapGrid::from_layers(width, height, vec![GridLayer { color: None, rtree }])
+    }
+
+    /// Create a grid from multiple layers, with earlier layers taking precedence for colors
+    pub fn from_layers(width: f64, height: f64, layers: Vec<GridLayer<T>>) -> MapGrid<T> {
+        let bbox = layers_extent(&layers);
+        MapGrid::create(width, height, layers, bbox, None)
+    }
+
+    /// Create a grid from multiple layers, only showing geometries within a viewport
+    pub fn with_viewport(
+        width: f64,
+        height: f64,
+        layers: Vec<GridLayer<T>>,
+        bbox: Rect<T>,
+    ) -> MapGrid<T> {
+        MapGrid::create(width, height, layers, bbox, Some(bbox))
+    }
+
+    /// Create a grid showing a viewport, optionally clipping geometries to it
+    fn create(
+        width: f64,
+        height: f64,
+        layers: Vec<GridLayer<T>>,
+        bbox: Rect<T>,
+        clip: Option<Rect<T>>,
+    ) -> MapGrid<T> {
+        let mut grid = MapGrid {
+            bbox,
+            clip,
+            rows: 0,
+            cols: 0,
+            cell_size: [0., 0.],
+            inner_cell_size: [0., 0.],
+            charset: Charset::Braille,
+            layers,
+        };
+        grid.set_viewport(width, height, bbox);
+        grid
+    }
+
+    /// Get the combined extent of geometries in all layers
+    pub fn extent(&self) -> Rect<T> {
+        layers_extent(&self.layers)
+    }
+
+    /// Get the current viewport
+    pub fn viewport(&self) -> Rect<T> {
+        self.bbox
+    }
+
+    /// Update the viewport and output dimensions without re-indexing geometries
+    pub fn set_viewport(&mut self, width: f64, height: f64, bbox: Rect<T>) {
         let box_width = bbox.width().to_f64().unwrap();
         let box_height = bbox.height().to_f64().unwrap();
 
@@ -157,40 +319,205 @@ where
             (true, true, _) | (false, _, _) => (width, (width / box_aspect_ratio) / 2.0),
         };
 
-        let cols = f64::ceil(width) as i32;
-        let rows = f64::ceil(height) as i32;
-
         // Get dimensions of individual cells
         let cell_width = box_width / cols_f;
         let cell_height = box_height / rows_f;
 
-        MapGrid {
-            bbox,
-            rows,
-            cols,
-            cell_size: [cell_width, cell_height],
-            inner_cell_size: [
-                cell_width / f64::from(CELL_COLS),
-                cell_height / f64::from(CELL_ROWS),
-            ],
-            rtree,
-        }
+        self.bbox = bbox;
+        self.cols = f64::ceil(width) as i32;
+        self.rows = f64::ceil(height) as i32;
+        self.cell_size = [cell_width, cell_height];
+        self.set_charset(self.charset);
     }
 
-    /// Iterate through cells, printing one line at a time
+    /// Update the characters used to print cells, which changes the number of sub-cells
+    pub fn set_charset(&mut self, charset: Charset) {
+        self.charset = charset;
+        self.inner_cell_size = [
+            self.cell_size[0] / f64::from(charset.cols()),
+            self.cell_size[1] / f64::from(charset.rows()),
+        ];
+    }
+
+    /// Print the grid to stdout
     pub fn print(&self) -> Result<()> {
         let stdout = io::stdout();
-        let mut handle = io::BufWriter::new(stdout.lock());
+        self.write_to(&mut io::BufWriter::new(stdout.lock()))
+    }
 
+    /// Iterate through cells, writing one line at a time
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
         for r in 0..self.rows {
-            let mut row_str = "".to_string();
-            for c in 0..self.cols {
-                let cell_value = self.query_cell_value(r, c);
-                row_str.push_str(&braille_char(cell_value).to_string());
+            writeln!(writer, "{}", self.render_row(r)).context("Error printing line")?;
+        }
+        writer.flush().context("Error printing line")
+    }
+
+    /// Render the grid as a string with a line for each row
+    pub fn render(&self) -> String {
+        (0..self.rows).map(|r| self.render_row(r) + "\n").collect()
+    }
+
+    /// Render the characters for a single row of the grid
+    pub fn render_row(&self, row: i32) -> String {
+        let mut row_str = "".to_string();
+        for c in 0..self.cols {
+            let (cell_value, color) = self.query_cell(row, c);
+            match color {
+                Some(color) => row_str.push_str(
+                    &Style::new()
+                        .color256(color)
+                        .apply_to(self.charset.char(cell_value))
+                        .to_string(),
+                ),
+                None => row_str.push(self.charset.char(cell_value)),
             }
-            writeln!(handle, "{}", row_str).context("Error printing line")?;
         }
-        Ok(())
+        row_str
+    }
+
+    /// Render geometries in the viewport as an SVG document, scaled so that the image has the
+    /// same aspect ratio as the printed grid
+    pub fn to_svg(&self) -> String {
+        let min_x = self.bbox.min().x.to_f64().unwrap();
+        let max_y = self.bbox.max().y.to_f64().unwrap();
+        let scale = [
+            SVG_CELL_SIZE[0] / self.cell_size[0],
+            SVG_CELL_SIZE[1] / self.cell_size[1],
+        ];
+        let svg_xy = |c: Coord<T>| {
+            (
+                (c.x.to_f64().unwrap() - min_x) * scale[0],
+                (max_y - c.y.to_f64().unwrap()) * scale[1],
+            )
+        };
+        let svg_coord = |c: Coord<T>| {
+            let (x, y) = svg_xy(c);
+            format!("{:.2} {:.2}", x, y)
+        };
+        let ring_path = |ring: &LineString<T>| {
+            let points = ring.coords().map(|c| svg_coord(*c)).collect::<Vec<_>>();
+            format!("M{}Z", points.join("L"))
+        };
+
+        let width = self.bbox.width().to_f64().unwrap() * scale[0];
+        let height = self.bbox.height().to_f64().unwrap() * scale[1];
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.0}\" height=\"{:.0}\" viewBox=\"0 0 {:.2} {:.2}\">\n",
+            width, height, width, height
+        );
+
+        let envelope = AABB::from_corners(
+            [self.bbox.min().x, self.bbox.min().y],
+            [self.bbox.max().x, self.bbox.max().y],
+        );
+        // Draw layers in reverse so that earlier layers are on top like in printed colors
+        for layer in self.layers.iter().rev() {
+            let (mut polygons, mut lines, mut points) = (vec![], vec![], vec![]);
+            for geom in layer.rtree.locate_in_envelope_intersecting(&envelope) {
+                match geom {
+                    GridGeom::Point(pt) => points.push(svg_xy(pt.0)),
+                    GridGeom::Line(line) => lines.push(format!(
+                        "M{}L{}",
+                        svg_coord(line.start),
+                        svg_coord(line.end)
+                    )),
+                    GridGeom::Polygon(poly) => polygons.extend(
+                        std::iter::once(poly.exterior())
+                            .chain(poly.interiors())
+                            .map(ring_path),
+                    ),
+                }
+            }
+            if polygons.is_empty() && lines.is_empty() && points.is_empty() {
+                continue;
+            }
+
+            let color = layer.color.map_or(DEFAULT_EXPORT_COLOR.to_string(), |c| {
+                rgb_to_hex(color256_to_rgb(c))
+            });
+            svg.push_str(&format!("<g fill=\"{}\" stroke=\"{}\">\n", color, color));
+            if !polygons.is_empty() {
+                svg.push_str(&format!(
+                    "<path d=\"{}\" stroke=\"none\" fill-rule=\"evenodd\"/>\n",
+                    polygons.concat()
+                ));
+            }
+            if !lines.is_empty() {
+                svg.push_str(&format!(
+                    "<path d=\"{}\" fill=\"none\" stroke-width=\"1\" stroke-linecap=\"round\"/>\n",
+                    lines.concat()
+                ));
+            }
+            for (x, y) in points {
+                svg.push_str(&format!(
+                    "<circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"{:.2}\" stroke=\"none\"/>\n",
+                    x, y, SVG_POINT_RADIUS
+                ));
+            }
+            svg.push_str("</g>\n");
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// Rasterize the grid as a PNG with each character as 2x4 squares the size of Braille dots,
+    /// filling sub-cells with layer colors if available and the foreground color otherwise
+    pub fn to_png(
+        &self,
+        dot_size: u32,
+        foreground: (u8, u8, u8),
+        background: (u8, u8, u8),
+    ) -> Result<Vec<u8>> {
+        let cell_width = PNG_CELL_DOTS[0] * dot_size;
+        let cell_height = PNG_CELL_DOTS[1] * dot_size;
+        let width = self.cols as u32 * cell_width;
+        let height = self.rows as u32 * cell_height;
+        let mut pixels = vec![background; (width * height) as usize];
+
+        let (sub_cols, sub_rows) = (self.charset.cols() as u32, self.charset.rows() as u32);
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                for (r, c, layer_idx) in self.query_inner_cells(row, col) {
+                    let color = self.layers[layer_idx]
+                        .color
+                        .map_or(foreground, color256_to_rgb);
+                    // Sub-cell bounds are rounded to pixels since they might not divide evenly
+                    let (c, r) = (c as u32, r as u32);
+                    let start_x = col as u32 * cell_width;
+                    let start_y = row as u32 * cell_height;
+                    let (min_x, max_x) = (
+                        start_x + c * cell_width / sub_cols,
+                        start_x + (c + 1) * cell_width / sub_cols,
+                    );
+                    let (min_y, max_y) = (
+                        start_y + r * cell_height / sub_rows,
+                        start_y + (r + 1) * cell_height / sub_rows,
+                    );
+                    for y in min_y..max_y {
+                        for x in min_x..max_x {
+                            pixels[(y * width + x) as usize] = color;
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut data = vec![];
+        let mut encoder = png::Encoder::new(&mut data, width, height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().context("Error writing PNG header")?;
+        writer
+            .write_image_data(
+                &pixels
+                    .into_iter()
+                    .flat_map(|(r, g, b)| [r, g, b])
+                    .collect::<Vec<u8>>(),
+            )
+            .context("Error writing PNG data")?;
+        writer.finish().context("Error writing PNG")?;
+        Ok(data)
     }
 
     // Get the minimum and maximum points of a cell
@@ -212,26 +539,48 @@ where
         (min_pt, max_pt)
     }
 
-    // Determine whether any geometries within a cell intersect
-    fn cell_intersects(&self, min_pt: Point<T>, max_pt: Point<T>) -> bool {
+    // Get the index of the first layer with geometries intersecting a cell
+    fn cell_layer(&self, min_pt: Point<T>, max_pt: Point<T>) -> Option<usize> {
+        // Cells can extend past the viewport if its aspect ratio differs from the output's
+        let (min_pt, max_pt) = match self.clip {
+            Some(clip) => {
+                let min_pt = Point::new(min_pt.x().max(clip.min().x), min_pt.y().max(clip.min().y));
+                let max_pt = Point::new(max_pt.x().min(clip.max().x), max_pt.y().min(clip.max().y));
+                if min_pt.x() >= max_pt.x() || min_pt.y() >= max_pt.y() {
+                    return None;
+                }
+                (min_pt, max_pt)
+            }
+            None => (min_pt, max_pt),
+        };
         let envelope = AABB::from_corners([min_pt.x(), min_pt.y()], [max_pt.x(), max_pt.y()]);
         let poly_bounds = Polygon::from(Rect::new(min_pt, max_pt));
 
-        self.rtree
-            .locate_in_envelope_intersecting(&envelope)
-            .skip_while(|g| match g {
-                GridGeom::Point(pt) => !poly_bounds.contains(pt),
-                GridGeom::Line(line) => !poly_bounds.intersects(line),
-                GridGeom::Polygon(poly) => !poly_bounds.intersects(poly),
-            })
-            .take(1)
-            .next()
-            .is_some()
+        self.layers.iter().position(|layer| {
+            layer
+                .rtree
+                .locate_in_envelope_intersecting(&envelope)
+                .skip_while(|g| match g {
+                    GridGeom::Point(pt) => !poly_bounds.contains(pt),
+                    GridGeom::Line(line) => !poly_bounds.intersects(line),
+                    GridGeom::Polygon(poly) => !poly_bounds.intersects(poly),
+                })
+                .take(1)
+                .next()
+                .is_some()
+        })
     }
 
-    /// For a given Braille 2x4 cell, query which cells have geometries in them
+    /// For a given cell, query which sub-cells have geometries in them
+    #[cfg(test)]
     fn query_cell_value(&self, row: i32, col: i32) -> u32 {
-        let mut cell_value = 0x00;
+        self.query_cell(row, col).0
+    }
+
+    /// Get the inner cells of a cell that have geometries in them, along with the index
+    /// of the first layer with geometries in each
+    fn query_inner_cells(&self, row: i32, col: i32) -> Vec<(i32, i32, usize)> {
+        let mut inner_cells = vec![];
 
         let bbox_min_x = self.bbox.min().x.to_f64().unwrap();
         let bbox_max_y = self.bbox.max().y.to_f64().unwrap();
@@ -239,26 +588,46 @@ where
         // Return early if there are no geometries in the outer cell
         let (outer_min_pt, outer_max_pt) =
             self.min_max_points(row, col, bbox_min_x, bbox_max_y, self.cell_size);
-        if !self.cell_intersects(outer_min_pt, outer_max_pt) {
-            return cell_value;
+        if self.cell_layer(outer_min_pt, outer_max_pt).is_none() {
+            return inner_cells;
         }
 
         // Get the start offset dimensions based on the outer row and column
         let start_width = (self.cell_size[0] * f64::from(col)) + bbox_min_x;
         let start_height = bbox_max_y - (self.cell_size[1] * f64::from(row));
 
-        for r in 0..CELL_ROWS {
-            for c in 0..CELL_COLS {
+        for r in 0..self.charset.rows() {
+            for c in 0..self.charset.cols() {
                 let (min_pt, max_pt) =
                     self.min_max_points(r, c, start_width, start_height, self.inner_cell_size);
-                // Add the associated cell value if intersecting lines are found
-                if self.cell_intersects(min_pt, max_pt) {
-                    cell_value += braille_cell_value(r, c);
+                // Add the inner cell if intersecting lines are found
+                if let Some(layer_idx) = self.cell_layer(min_pt, max_pt) {
+                    inner_cells.push((r, c, layer_idx));
                 }
             }
         }
+        inner_cells
+    }
+
+    /// Get the character value of a cell along with the color of the layer that fills the most
+    /// inner cells
+    fn query_cell(&self, row: i32, col: i32) -> (u32, Option<u8>) {
+        let mut cell_value = 0x00;
+        let mut layer_counts = vec![0; self.layers.len()];
+        for (r, c, layer_idx) in self.query_inner_cells(row, col) {
+            cell_value += self.charset.cell_value(r, c);
+            layer_counts[layer_idx] += 1;
+        }
 
-        cell_value
+        // Use the first layer with the most inner cells to break ties
+        let color = layer_counts
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, count)| **count > 0)
+            .max_by_key(|(_, count)| **count)
+            .and_then(|(idx, _)| self.layers[idx].color);
+        (cell_value, color)
     }
 }
 
@@ -273,7 +642,12 @@ mod test {
     fn test_vec_from_geom() {
         let poly = Polygon::new(LineString::from(vec![(0., 0.), (1., 1.), (1., 0.)]), vec![]);
         assert_eq!(
-            GridGeom::<f64>::vec_from_geom(Geometry::Polygon(poly.clone()), 0.01, false),
+            GridGeom::<f64>::vec_from_geom(
+                Geometry::Polygon(poly.clone()),
+                0.01,
+                false,
+                Rings::All
+            ),
             vec![
                 GridGeom::Line(Line::<f64>::new((0., 0.), (1., 1.))),
                 GridGeom::Line(Line::<f64>::new((1., 1.), (1., 0.))),
@@ -281,9 +655,48 @@ mod test {
             ]
         );
         assert_eq!(
-            GridGeom::<f64>::vec_from_geom(Geometry::Polygon(poly.clone()), 0.01, true),
+            GridGeom::<f64>::vec_from_geom(Geometry::Polygon(poly.clone()), 0.01, true, Rings::All),
             vec![GridGeom::Polygon(poly)]
         );
+        let empty = Polygon::new(LineString::<f64>::new(vec![]), vec![]);
+        assert!(
+            GridGeom::vec_from_geom(Geometry::Polygon(empty.clone()), 0., true, Rings::All)
+                .is_empty()
+        );
+        assert_eq!(GridGeom::Polygon(empty).envelope(), AABB::new_empty());
+    }
+
+    #[test]
+    fn test_vec_from_geom_rings() {
+        let poly = Polygon::new(
+            LineString::from(vec![(0., 0.), (4., 0.), (4., 4.), (0., 4.)]),
+            vec![LineString::from(vec![(1., 1.), (2., 1.), (2., 2.)])],
+        );
+        let interior = vec![
+            GridGeom::Line(Line::<f64>::new((1., 1.), (2., 1.))),
+            GridGeom::Line(Line::<f64>::new((2., 1.), (2., 2.))),
+            GridGeom::Line(Line::<f64>::new((2., 2.), (1., 1.))),
+        ];
+        let exterior = GridGeom::<f64>::vec_from_geom(
+            Geometry::Polygon(poly.clone()),
+            0.,
+            false,
+            Rings::Exterior,
+        );
+        assert_eq!(exterior.len(), 4);
+        assert_eq!(
+            GridGeom::<f64>::vec_from_geom(
+                Geometry::Polygon(poly.clone()),
+                0.,
+                false,
+                Rings::Interior
+            ),
+            interior
+        );
+        assert_eq!(
+            GridGeom::<f64>::vec_from_geom(Geometry::Polygon(poly), 0., false, Rings::All),
+            [exterior, interior].concat()
+        );
     }
 
     #[test]
@@ -306,6 +719,109 @@ mod test {
         assert_eq!(grid.query_cell_value(0, 0), 0x36);
     }
 
+    #[test]
+    fn write_to_renders_rows() {
+        let rtree = RTree::bulk_load(vec![GridGeom::Line(Line::new([0., 0.], [4., 2.]))]);
+        let grid = MapGrid::new(4., 2., rtree);
+        let mut output = vec![];
+        grid.write_to(&mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), grid.render());
+        assert_eq!(grid.render(), "⠀⠀⠀⢀\n⣠⡴⠞⠋\n");
+    }
+
+    #[test]
+    fn with_viewport_clips_geometries() {
+        let rtree = RTree::bulk_load(vec![
+            GridGeom::Line(Line::new([0.5, -1.5], [4., -1.5])),
+            GridGeom::Point(Point::new(10., 10.)),
+        ]);
+        let layers = vec![GridLayer { color: None, rtree }];
+        let grid = MapGrid::with_viewport(4., 4., layers, Rect::new((-4., -4.), (4., 4.)));
+        assert_eq!(grid.extent(), Rect::new((0.5, -1.5), (10., 10.)));
+        assert_eq!(grid.query_cell_value(0, 3), 0x00);
+        assert_eq!(grid.query_cell_value(1, 2), 0x12);
+        assert_eq!(grid.query_cell_value(1, 0), 0x00);
+
+        // Rows below the viewport are left empty when it's wider than the output
+        let rtree = RTree::bulk_load(vec![
+            GridGeom::Line(Line::new([0., 0.], [0., -10.])),
+            GridGeom::Point(Point::new(2., -8.)),
+        ]);
+        let layers = vec![GridLayer { color: None, rtree }];
+        let grid = MapGrid::with_viewport(8., 8., layers, Rect::new((-4., -4.), (4., 4.)));
+        assert_ne!(grid.query_cell_value(3, 4), 0x00);
+        for row in 4..8 {
+            for col in 0..8 {
+                assert_eq!(grid.query_cell_value(row, col), 0x00);
+            }
+        }
+    }
+
+    #[test]
+    fn to_svg_draws_geometries() {
+        let rtree = RTree::bulk_load(vec![
+            GridGeom::Line(Line::new([0., 0.], [4., 0.])),
+            GridGeom::Point(Point::new(0., 1.)),
+            GridGeom::Point(Point::new(10., 10.)),
+        ]);
+        let layers = vec![GridLayer {
+            color: Some(9),
+            rtree,
+        }];
+        let grid = MapGrid::with_viewport(4., 4., layers, Rect::new((0., 0.), (4., 2.)));
+        assert_eq!(
+            grid.to_svg(),
+            [
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"32\" height=\"16\" viewBox=\"0 0 32.00 16.00\">",
+                "<g fill=\"#ff0000\" stroke=\"#ff0000\">",
+                "<path d=\"M0.00 16.00L32.00 16.00\" fill=\"none\" stroke-width=\"1\" stroke-linecap=\"round\"/>",
+                "<circle cx=\"0.00\" cy=\"8.00\" r=\"2.00\" stroke=\"none\"/>",
+                "</g>",
+                "</svg>\n",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn to_png_rasterizes_cells() {
+        let rtree = RTree::bulk_load(vec![
+            GridGeom::Line(Line::new([0., 0.], [4., 0.])),
+            GridGeom::Point(Point::new(0., 1.)),
+        ]);
+        let grid = MapGrid::new(4., 4., rtree);
+        let data = grid.to_png(2, (0, 0, 0), (255, 255, 255)).unwrap();
+        assert_eq!(data, grid.to_png(2, (0, 0, 0), (255, 255, 255)).unwrap());
+
+        let mut reader = png::Decoder::new(std::io::Cursor::new(data))
+            .read_info()
+            .unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size().unwrap()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height), (16, 32));
+        // First row of dots is empty, and the first dot of the second row has the point
+        assert_eq!(pixels[..3], [255, 255, 255]);
+        let second_row = 2 * info.line_size;
+        assert_eq!(pixels[second_row..second_row + 3], [0, 0, 0]);
+    }
+
+    #[test]
+    fn query_cell_uses_majority_layer_color() {
+        let layers = vec![
+            GridLayer {
+                color: Some(1),
+                rtree: RTree::bulk_load(vec![GridGeom::Point(Point::new(0., 1.))]),
+            },
+            GridLayer {
+                color: Some(2),
+                rtree: RTree::bulk_load(vec![GridGeom::Line(Line::new([0., 0.], [4., 0.]))]),
+            },
+        ];
+        let grid = MapGrid::from_layers(4., 4., layers);
+        assert_eq!(grid.query_cell(0, 0), (0x36, Some(2)));
+        assert_eq!(grid.query_cell(3, 3), (0x00, None));
+    }
+
     #[test]
     fn min_max_points() {
         let rtree = RTree::bulk_load(vec![
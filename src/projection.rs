@@ -0,0 +1,222 @@
+//! Map projections applied to lon/lat coordinates before printing
+
+use std::f64::consts::{FRAC_PI_4, PI};
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use geo::{BoundingRect, Coord, LineString, MapCoords, Rect};
+
+use crate::map_grid::GridGeom;
+
+/// Radius of the WGS84 ellipsoid in meters, used as a sphere for all projections
+const EARTH_RADIUS: f64 = 6_378_137.;
+
+/// Maximum latitude shown in Web Mercator
+const MERCATOR_MAX_LAT: f64 = 85.051_128_78;
+
+/// Equal Earth polynomial coefficients
+const EQUAL_EARTH_A1: f64 = 1.340_264;
+const EQUAL_EARTH_A2: f64 = -0.081_106;
+const EQUAL_EARTH_A3: f64 = 0.000_893;
+const EQUAL_EARTH_A4: f64 = 0.003_796;
+
+/// Default Albers parameters, matching the USGS contiguous United States projection
+const ALBERS_PARALLELS: (f64, f64) = (29.5, 45.5);
+const ALBERS_ORIGIN: (f64, f64) = (-96., 23.);
+
+/// Number of points sampled along each edge when projecting a bounding box
+const RECT_EDGE_SAMPLES: usize = 16;
+
+/// Projection applied to lon/lat coordinates before printing
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// Lon/lat coordinates printed as they are
+    PlateCarree,
+    /// Spherical mercator used by web maps
+    WebMercator,
+    /// Equal Earth equal-area pseudocylindrical projection
+    EqualEarth,
+    /// Albers equal-area conic with standard parallels and origin as (lon, lat)
+    Albers {
+        /// Standard parallels in degrees
+        parallels: (f64, f64),
+        /// Longitude and latitude of the origin in degrees
+        origin: (f64, f64),
+    },
+}
+
+impl FromStr for Projection {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Projection> {
+        match s.to_ascii_lowercase().as_ref() {
+            "plate-carree" => Ok(Projection::PlateCarree),
+            "web-mercator" => Ok(Projection::WebMercator),
+            "equal-earth" => Ok(Projection::EqualEarth),
+            "albers" => Ok(Projection::Albers {
+                parallels: ALBERS_PARALLELS,
+                origin: ALBERS_ORIGIN,
+            }),
+            p => Err(anyhow::anyhow!("Invalid projection supplied: {}", p)),
+        }
+    }
+}
+
+/// Parse Albers standard parallels from a comma-separated "lat1,lat2" string
+pub fn get_parallels(parallels: &str) -> Result<(f64, f64)> {
+    let lats = parallels
+        .split(',')
+        .map(|v| v.trim().parse::<f64>())
+        .collect::<Result<Vec<f64>, _>>()
+        .with_context(|| format!("Could not parse parallels value: {}", parallels))?;
+    match lats[..] {
+        [lat1, lat2] if lat1 + lat2 != 0. => Ok((lat1, lat2)),
+        [_, _] => Err(anyhow::anyhow!(
+            "Parallels cannot be symmetric around the equator: {}",
+            parallels
+        )),
+        _ => Err(anyhow::anyhow!(
+            "Parallels must have two values (lat1,lat2): {}",
+            parallels
+        )),
+    }
+}
+
+impl Projection {
+    /// Create an Albers projection with custom standard parallels, centered between them
+    pub fn albers(parallels: (f64, f64)) -> Projection {
+        Projection::Albers {
+            parallels,
+            origin: (ALBERS_ORIGIN.0, (parallels.0 + parallels.1) / 2.),
+        }
+    }
+
+    /// Project a lon/lat coordinate
+    pub fn project(&self, coord: Coord<f64>) -> Coord<f64> {
+        let lon = coord.x.to_radians();
+        match self {
+            Projection::PlateCarree => coord,
+            Projection::WebMercator => {
+                let lat = coord
+                    .y
+                    .clamp(-MERCATOR_MAX_LAT, MERCATOR_MAX_LAT)
+                    .to_radians();
+                Coord {
+                    x: EARTH_RADIUS * lon,
+                    y: EARTH_RADIUS * (FRAC_PI_4 + lat / 2.).tan().ln(),
+                }
+            }
+            Projection::EqualEarth => {
+                let theta = ((3_f64.sqrt() / 2.) * coord.y.to_radians().sin()).asin();
+                let theta2 = theta * theta;
+                let theta6 = theta2 * theta2 * theta2;
+                Coord {
+                    x: EARTH_RADIUS * 2. * 3_f64.sqrt() * lon * theta.cos()
+                        / (3.
+                            * (9. * EQUAL_EARTH_A4 * theta6 * theta2
+                                + 7. * EQUAL_EARTH_A3 * theta6
+                                + 3. * EQUAL_EARTH_A2 * theta2
+                                + EQUAL_EARTH_A1)),
+                    y: EARTH_RADIUS
+                        * theta
+                        * (EQUAL_EARTH_A1
+                            + EQUAL_EARTH_A2 * theta2
+                            + theta6 * (EQUAL_EARTH_A3 + EQUAL_EARTH_A4 * theta2)),
+                }
+            }
+            Projection::Albers { parallels, origin } => {
+                let (lat1, lat2) = (parallels.0.to_radians(), parallels.1.to_radians());
+                let n = (lat1.sin() + lat2.sin()) / 2.;
+                let c = lat1.cos().powi(2) + 2. * n * lat1.sin();
+                let rho = |lat: f64| EARTH_RADIUS * (c - 2. * n * lat.sin()).sqrt() / n;
+                let rho0 = rho(origin.1.to_radians());
+                let theta = n * normalize_lon(lon - origin.0.to_radians());
+                let rho_lat = rho(coord.y.to_radians());
+                Coord {
+                    x: rho_lat * theta.sin(),
+                    y: rho0 - rho_lat * theta.cos(),
+                }
+            }
+        }
+    }
+
+    /// Project all coordinates of a grid geometry
+    pub fn project_geom(&self, geom: GridGeom<f64>) -> GridGeom<f64> {
+        if *self == Projection::PlateCarree {
+            return geom;
+        }
+        let project = |c| self.project(c);
+        match geom {
+            GridGeom::Point(pt) => GridGeom::Point(pt.map_coords(project)),
+            GridGeom::Line(line) => GridGeom::Line(line.map_coords(project)),
+            GridGeom::Polygon(poly) => GridGeom::Polygon(poly.map_coords(project)),
+        }
+    }
+
+    /// Project a lon/lat bounding box, sampling its edges since they may curve when projected
+    pub fn project_rect(&self, rect: Rect<f64>) -> Rect<f64> {
+        if *self == Projection::PlateCarree {
+            return rect;
+        }
+        let (min, max) = (rect.min(), rect.max());
+        let lerp = |a: f64, b: f64, idx: usize| a + (b - a) * idx as f64 / RECT_EDGE_SAMPLES as f64;
+        let coords = (0..=RECT_EDGE_SAMPLES)
+            .flat_map(|idx| {
+                let (x, y) = (lerp(min.x, max.x, idx), lerp(min.y, max.y, idx));
+                [
+                    Coord { x, y: min.y },
+                    Coord { x, y: max.y },
+                    Coord { x: min.x, y },
+                    Coord { x: max.x, y },
+                ]
+            })
+            .map(|c| self.project(c))
+            .collect::<Vec<_>>();
+        LineString::new(coords).bounding_rect().unwrap()
+    }
+}
+
+/// Wrap a longitude difference in radians to the range -PI to PI
+fn normalize_lon(lon: f64) -> f64 {
+    (lon + PI).rem_euclid(2. * PI) - PI
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use float_cmp::approx_eq;
+
+    #[test]
+    fn test_web_mercator() {
+        let projected = Projection::WebMercator.project(Coord { x: 180., y: 90. });
+        assert!(approx_eq!(f64, projected.x, 20_037_508.34, epsilon = 0.01));
+        assert!(approx_eq!(f64, projected.y, 20_037_508.34, epsilon = 1.));
+    }
+
+    #[test]
+    fn test_equal_earth() {
+        let origin = Projection::EqualEarth.project(Coord { x: 0., y: 0. });
+        assert_eq!(origin, Coord { x: 0., y: 0. });
+        let edge = Projection::EqualEarth.project(Coord { x: 180., y: 0. });
+        assert!(approx_eq!(f64, edge.x, 17_263_256.84, epsilon = 1.));
+    }
+
+    #[test]
+    fn test_albers() {
+        let albers: Projection = "albers".parse().unwrap();
+        let origin = albers.project(Coord { x: -96., y: 23. });
+        assert!(approx_eq!(f64, origin.x, 0., epsilon = 1e-6));
+        assert!(approx_eq!(f64, origin.y, 0., epsilon = 1e-6));
+        let west = albers.project(Coord { x: -100., y: 40. });
+        let east = albers.project(Coord { x: -92., y: 40. });
+        assert!(approx_eq!(f64, west.x, -east.x, epsilon = 1e-6));
+        assert!(approx_eq!(f64, west.y, east.y, epsilon = 1e-6));
+    }
+
+    #[test]
+    fn test_get_parallels() {
+        assert_eq!(get_parallels("20,50").unwrap(), (20., 50.));
+        assert!(get_parallels("20").is_err());
+        assert!(get_parallels("-30,30").is_err());
+    }
+}
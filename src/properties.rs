@@ -0,0 +1,195 @@
+//! Feature properties normalized across input formats
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use geojson::JsonValue;
+use geozero::{ColumnValue, PropertyProcessor};
+use rusqlite::types::ValueRef;
+use shapefile::dbase::FieldValue;
+
+/// Feature properties keyed by name
+pub type Properties = BTreeMap<String, PropValue>;
+
+/// Property value normalized across input formats
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropValue {
+    /// Missing or null value
+    Null,
+    /// Boolean value
+    Bool(bool),
+    /// Number, with integers stored as floats
+    Number(f64),
+    /// Text value
+    String(String),
+}
+
+impl PropValue {
+    /// Parse a value from text, treating empty strings as null and reading numbers if possible
+    pub fn from_text(s: &str) -> PropValue {
+        let s = s.trim();
+        if s.is_empty() {
+            PropValue::Null
+        } else if let Ok(num) = s.parse::<f64>() {
+            PropValue::Number(num)
+        } else {
+            PropValue::String(s.to_string())
+        }
+    }
+
+    /// Get the numeric value if the property is a number
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            PropValue::Number(num) => Some(*num),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for PropValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PropValue::Null => write!(f, "null"),
+            PropValue::Bool(b) => write!(f, "{}", b),
+            PropValue::Number(num) => write!(f, "{}", num),
+            PropValue::String(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl From<&JsonValue> for PropValue {
+    fn from(value: &JsonValue) -> PropValue {
+        match value {
+            JsonValue::Null => PropValue::Null,
+            JsonValue::Bool(b) => PropValue::Bool(*b),
+            JsonValue::Number(num) => num.as_f64().map_or(PropValue::Null, PropValue::Number),
+            JsonValue::String(s) => PropValue::String(s.clone()),
+            // Nested values are kept as their JSON representation
+            v => PropValue::String(v.to_string()),
+        }
+    }
+}
+
+impl From<&PropValue> for JsonValue {
+    fn from(value: &PropValue) -> JsonValue {
+        match value {
+            PropValue::Null => JsonValue::Null,
+            PropValue::Bool(b) => JsonValue::Bool(*b),
+            // Whole numbers are written as integers so that IDs don't gain a decimal point
+            PropValue::Number(num) if num.fract() == 0. && num.abs() < i64::MAX as f64 => {
+                JsonValue::from(*num as i64)
+            }
+            PropValue::Number(num) => JsonValue::from(*num),
+            PropValue::String(s) => JsonValue::String(s.clone()),
+        }
+    }
+}
+
+impl From<FieldValue> for PropValue {
+    fn from(value: FieldValue) -> PropValue {
+        match value {
+            FieldValue::Character(Some(s)) | FieldValue::Memo(s) => PropValue::String(s),
+            FieldValue::Numeric(Some(num))
+            | FieldValue::Currency(num)
+            | FieldValue::Double(num) => PropValue::Number(num),
+            FieldValue::Float(Some(num)) => PropValue::Number(f64::from(num)),
+            FieldValue::Integer(num) => PropValue::Number(f64::from(num)),
+            FieldValue::Logical(Some(b)) => PropValue::Bool(b),
+            FieldValue::Date(Some(date)) => PropValue::String(date.to_string()),
+            FieldValue::DateTime(date_time) => PropValue::String(format!(
+                "{} {:02}:{:02}:{:02}",
+                date_time.date().to_string(),
+                date_time.time().hours(),
+                date_time.time().minutes(),
+                date_time.time().seconds()
+            )),
+            _ => PropValue::Null,
+        }
+    }
+}
+
+impl From<ValueRef<'_>> for PropValue {
+    fn from(value: ValueRef) -> PropValue {
+        match value {
+            ValueRef::Integer(num) => PropValue::Number(num as f64),
+            ValueRef::Real(num) => PropValue::Number(num),
+            ValueRef::Text(s) => PropValue::String(String::from_utf8_lossy(s).to_string()),
+            ValueRef::Null | ValueRef::Blob(_) => PropValue::Null,
+        }
+    }
+}
+
+impl From<&ColumnValue<'_>> for PropValue {
+    fn from(value: &ColumnValue) -> PropValue {
+        match value {
+            ColumnValue::Byte(num) => PropValue::Number(f64::from(*num)),
+            ColumnValue::UByte(num) => PropValue::Number(f64::from(*num)),
+            ColumnValue::Bool(b) => PropValue::Bool(*b),
+            ColumnValue::Short(num) => PropValue::Number(f64::from(*num)),
+            ColumnValue::UShort(num) => PropValue::Number(f64::from(*num)),
+            ColumnValue::Int(num) => PropValue::Number(f64::from(*num)),
+            ColumnValue::UInt(num) => PropValue::Number(f64::from(*num)),
+            ColumnValue::Long(num) => PropValue::Number(*num as f64),
+            ColumnValue::ULong(num) => PropValue::Number(*num as f64),
+            ColumnValue::Float(num) => PropValue::Number(f64::from(*num)),
+            ColumnValue::Double(num) => PropValue::Number(*num),
+            ColumnValue::String(s) | ColumnValue::Json(s) | ColumnValue::DateTime(s) => {
+                PropValue::String(s.to_string())
+            }
+            ColumnValue::Binary(_) => PropValue::Null,
+        }
+    }
+}
+
+/// Collects properties from geozero feature readers
+#[derive(Debug, Default)]
+pub struct PropertyCollector(pub Properties);
+
+impl PropertyProcessor for PropertyCollector {
+    fn property(
+        &mut self,
+        _idx: usize,
+        name: &str,
+        value: &ColumnValue,
+    ) -> geozero::error::Result<bool> {
+        self.0.insert(name.to_string(), PropValue::from(value));
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_text() {
+        assert_eq!(PropValue::from_text(" "), PropValue::Null);
+        assert_eq!(PropValue::from_text("1.5"), PropValue::Number(1.5));
+        assert_eq!(
+            PropValue::from_text("IL"),
+            PropValue::String("IL".to_string())
+        );
+    }
+
+    #[test]
+    fn test_from_json() {
+        assert_eq!(
+            PropValue::from(&JsonValue::from(10000)),
+            PropValue::Number(10000.)
+        );
+        assert_eq!(
+            PropValue::from(&JsonValue::from(vec![1, 2])),
+            PropValue::String("[1,2]".to_string())
+        );
+    }
+
+    #[test]
+    fn test_to_json() {
+        assert_eq!(
+            JsonValue::from(&PropValue::Number(10000.)).to_string(),
+            "10000"
+        );
+        assert_eq!(JsonValue::from(&PropValue::Number(1.5)).to_string(), "1.5");
+        assert_eq!(JsonValue::from(&PropValue::Null), JsonValue::Null);
+    }
+}
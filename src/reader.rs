@@ -0,0 +1,556 @@
+//! The `GeoReader` trait, format detection and reading features into grid geometries
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, Read};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use geo::Geometry;
+
+use crate::filter::Filter;
+use crate::input::{
+    CsvReader, FlatGeobufReader, GeoJsonReader, GeoJsonSeqReader, GeoPackageReader, GpxReader,
+    HexWkbReader, KmlReader, PolylineReader, ShapefileReader, TopoJsonReader, WkbReader, WktReader,
+};
+use crate::map_grid::{GridFeature, Rings};
+use crate::properties::Properties;
+
+/// Number of bytes at the start of a file passed to readers to detect its format
+const HEADER_SIZE: usize = 8192;
+
+/// Magic bytes at the start of zip archives
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+/// Number of skipped feature indexes listed for each reason in summaries
+const SUMMARY_INDEXES: usize = 5;
+
+/// Geometry read from an input along with its properties
+#[derive(Debug, Clone, PartialEq)]
+pub struct Feature {
+    /// Geometry in lon/lat coordinates
+    pub geometry: Geometry<f64>,
+    /// Properties of the feature
+    pub properties: Properties,
+}
+
+impl Feature {
+    /// Create a feature from a geometry and its properties
+    pub fn new(geometry: Geometry<f64>, properties: Properties) -> Feature {
+        Feature {
+            geometry,
+            properties,
+        }
+    }
+}
+
+/// Iterator over the features of an input, which can fail for individual features
+pub type FeatureIter<'a> = Box<dyn Iterator<Item = Result<Feature>> + 'a>;
+
+/// Reader for an input format, which can be registered in a `ReaderRegistry` to make it
+/// available alongside the built-in formats
+pub trait GeoReader {
+    /// Names of the format used to select it, which are also matched against file extensions.
+    /// The first name is used in messages
+    fn names(&self) -> &[&str];
+
+    /// Check whether the first bytes of an input look like this format, used when the format
+    /// can't be detected from the file extension. The header is complete if it holds the whole
+    /// input, otherwise its last line may be cut off
+    fn detect(&self, _header: &[u8], _is_complete: bool) -> bool {
+        false
+    }
+
+    /// Read features from a file path or '-' for stdin
+    fn read(&self, file_path: &str) -> Result<FeatureIter<'_>>;
+
+    /// Get the name of the CRS declared in an input if the format supports one
+    fn crs(&self, _file_path: &str) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// Check whether features are read in WGS84 lon/lat, either because the format requires
+    /// it or because they're transformed from the input's CRS
+    fn is_lon_lat(&self, _file_path: &str) -> bool {
+        false
+    }
+
+    /// Get the names and types of attribute fields declared in an input if the format has a
+    /// schema, otherwise they're inferred from feature properties
+    fn fields(&self, _file_path: &str) -> Result<Option<Vec<(String, String)>>> {
+        Ok(None)
+    }
+}
+
+/// Readers for each supported input format, checked in order of registration with the most
+/// recently registered first
+pub struct ReaderRegistry {
+    readers: Vec<Box<dyn GeoReader>>,
+}
+
+impl Default for ReaderRegistry {
+    /// Create a registry with the built-in readers using their default options
+    fn default() -> ReaderRegistry {
+        let mut registry = ReaderRegistry::new();
+        registry.register(GeoJsonReader);
+        registry.register(GeoJsonSeqReader);
+        registry.register(TopoJsonReader);
+        registry.register(CsvReader::default());
+        registry.register(ShapefileReader);
+        registry.register(WktReader);
+        registry.register(PolylineReader::default());
+        registry.register(KmlReader);
+        registry.register(GpxReader::default());
+        registry.register(FlatGeobufReader::default());
+        registry.register(GeoPackageReader::default());
+        registry.register(WkbReader);
+        registry.register(HexWkbReader);
+        registry
+    }
+}
+
+impl ReaderRegistry {
+    /// Create an empty registry
+    pub fn new() -> ReaderRegistry {
+        ReaderRegistry { readers: vec![] }
+    }
+
+    /// Add a reader, which takes precedence over previously registered readers with the same
+    /// names so that built-in readers can be replaced or configured
+    pub fn register<R: GeoReader + 'static>(&mut self, reader: R) {
+        self.readers.insert(0, Box::new(reader));
+    }
+
+    /// Get a reader by one of its names, ignoring case
+    pub fn get(&self, name: &str) -> Option<&dyn GeoReader> {
+        self.readers
+            .iter()
+            .find(|r| r.names().iter().any(|n| n.eq_ignore_ascii_case(name)))
+            .map(|r| r.as_ref())
+    }
+
+    /// Get the reader for an input from the format if provided, otherwise from the file
+    /// extension or the first bytes of the input
+    pub fn detect(&self, file_path: &str, format: Option<&str>) -> Result<&dyn GeoReader> {
+        if let Some(format) = format {
+            return self
+                .get(format)
+                .with_context(|| format!("Invalid format supplied: {}", format));
+        }
+        let extension = Path::new(file_path).extension().and_then(|e| e.to_str());
+        if let Some(reader) = extension.and_then(|ext| self.get(ext)) {
+            return Ok(reader);
+        }
+
+        let (header, is_complete) = read_header(file_path)?;
+        if let Some(reader) = self.detect_header(&header, is_complete) {
+            return Ok(reader);
+        }
+        if header.starts_with(ZIP_MAGIC) {
+            return Err(anyhow::anyhow!(
+                "{} is a zip archive, extract it and read the files inside",
+                file_path
+            ));
+        }
+        // Default to GeoJSON when reading from stdin
+        match (file_path, self.get("geojson")) {
+            ("-", Some(reader)) => Ok(reader),
+            _ => Err(anyhow::anyhow!(
+                "Could not detect the format of {}, specify it with --format",
+                file_path
+            )),
+        }
+    }
+
+    /// Get the first reader that detects its format from the first bytes of an input
+    fn detect_header(&self, header: &[u8], is_complete: bool) -> Option<&dyn GeoReader> {
+        self.readers
+            .iter()
+            .find(|r| r.detect(header, is_complete))
+            .map(|r| r.as_ref())
+    }
+}
+
+/// Read the first bytes of a file, or peek at them in stdin so that they can still be read,
+/// along with whether they're the whole input
+fn read_header(file_path: &str) -> Result<(Vec<u8>, bool)> {
+    if file_path == "-" {
+        let mut stdin = io::stdin().lock();
+        let buffer = stdin
+            .fill_buf()
+            .context("There was an error reading from stdin")?;
+        // Peeking only returns what's buffered, so a short buffer is assumed to be the whole
+        // input, which holds for small inputs that are piped in at once
+        let header = buffer[..buffer.len().min(HEADER_SIZE)].to_vec();
+        let is_complete = header.len() < HEADER_SIZE;
+        return Ok((header, is_complete));
+    }
+    let mut header = vec![];
+    fs::File::open(file_path)
+        .with_context(|| format!("There was an error opening file: {}", file_path))?
+        .take(HEADER_SIZE as u64)
+        .read_to_end(&mut header)
+        .with_context(|| format!("There was an error reading from file: {}", file_path))?;
+    let is_complete = header.len() < HEADER_SIZE;
+    Ok((header, is_complete))
+}
+
+/// Reason a feature was skipped, which can be attached to reader errors as context. Errors
+/// without a reason are treated as parse errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SkipReason {
+    /// Feature or geometry couldn't be parsed
+    Parse,
+    /// Feature has no geometry
+    NullGeometry,
+    /// Geometry type can't be drawn
+    UnsupportedType,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SkipReason::Parse => write!(f, "parse error"),
+            SkipReason::NullGeometry => write!(f, "null geometry"),
+            SkipReason::UnsupportedType => write!(f, "unsupported type"),
+        }
+    }
+}
+
+impl std::error::Error for SkipReason {}
+
+impl SkipReason {
+    /// Get the reason attached to an error, defaulting to a parse error
+    pub fn from_error(error: &anyhow::Error) -> SkipReason {
+        error
+            .chain()
+            .find_map(|e| e.downcast_ref::<SkipReason>())
+            .copied()
+            .unwrap_or(SkipReason::Parse)
+    }
+}
+
+/// Feature that couldn't be read, with its index in the input
+#[derive(Debug)]
+pub struct SkippedFeature {
+    /// Index of the feature in the input
+    pub index: usize,
+    /// Reason the feature was skipped
+    pub reason: SkipReason,
+    /// Error returned by the reader
+    pub error: anyhow::Error,
+}
+
+/// Grid features read from an input, along with features that were skipped and the number
+/// that didn't match the filter
+pub struct InputFeatures {
+    /// Features that were read and matched the filter
+    pub features: Vec<GridFeature<f64>>,
+    /// Features that couldn't be read
+    pub skipped: Vec<SkippedFeature>,
+    /// Number of features that didn't match the filter
+    pub filtered: usize,
+}
+
+impl InputFeatures {
+    /// Number of features in the input, including skipped and filtered features
+    pub fn read_count(&self) -> usize {
+        self.features.len() + self.skipped.len() + self.filtered
+    }
+
+    /// Number of features with grid geometries to render
+    pub fn rendered_count(&self) -> usize {
+        self.features.iter().filter(|f| !f.geoms.is_empty()).count()
+    }
+
+    /// Summarize the features read, rendered and skipped by reason with the indexes of the
+    /// first few skipped features for each
+    pub fn summary(&self, file_path: &str) -> String {
+        let filtered = if self.filtered > 0 {
+            format!(", filtered out {}", self.filtered)
+        } else {
+            String::new()
+        };
+        format!(
+            "{}: read {} features{}, rendered {}, skipped {}{}",
+            file_path,
+            self.read_count(),
+            filtered,
+            self.rendered_count(),
+            self.skipped.len(),
+            skipped_summary(&self.skipped)
+        )
+    }
+}
+
+/// Summarize skipped features with a line for each reason, listing the indexes of the first
+/// few features and the first error
+pub fn skipped_summary(skipped: &[SkippedFeature]) -> String {
+    let mut by_reason: BTreeMap<SkipReason, Vec<&SkippedFeature>> = BTreeMap::new();
+    for feature in skipped {
+        by_reason.entry(feature.reason).or_default().push(feature);
+    }
+    let mut summary = String::new();
+    for (reason, skipped) in by_reason {
+        let indexes = skipped
+            .iter()
+            .take(SUMMARY_INDEXES)
+            .map(|s| s.index.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let more = if skipped.len() > SUMMARY_INDEXES {
+            ", ..."
+        } else {
+            ""
+        };
+        summary.push_str(&format!(
+            "\n  {}: {} (features {}{})",
+            reason,
+            skipped.len(),
+            indexes,
+            more
+        ));
+        if reason != SkipReason::NullGeometry {
+            summary.push_str(&format!("\n    first error: {:#}", skipped[0].error));
+        }
+    }
+    summary
+}
+
+/// Read all features from an input, passing each one to a callback. Features that can't be
+/// read are skipped unless strict, which returns the error for the first one. Features without
+/// geometries are always skipped
+pub fn read_features<F>(
+    reader: &dyn GeoReader,
+    file_path: &str,
+    strict: bool,
+    mut on_feature: F,
+) -> Result<Vec<SkippedFeature>>
+where
+    F: FnMut(Feature),
+{
+    let mut skipped = vec![];
+    for (index, feature) in reader.read(file_path)?.enumerate() {
+        match feature {
+            Ok(f) => on_feature(f),
+            Err(error) => {
+                let reason = SkipReason::from_error(&error);
+                if strict && reason != SkipReason::NullGeometry {
+                    return Err(error).with_context(|| {
+                        format!("Could not read feature {} in {}", index, file_path)
+                    });
+                }
+                skipped.push(SkippedFeature {
+                    index,
+                    reason,
+                    error,
+                });
+            }
+        }
+    }
+    Ok(skipped)
+}
+
+/// Read the features from an input that match the filter if provided and simplify them into
+/// grid geometries, skipping features that can't be read unless strict
+pub fn read_grid_features(
+    reader: &dyn GeoReader,
+    file_path: &str,
+    simplification: f64,
+    is_area: bool,
+    rings: Rings,
+    strict: bool,
+    filter: Option<&Filter>,
+) -> Result<InputFeatures> {
+    let mut features = vec![];
+    let mut filtered = 0;
+    let skipped = read_features(reader, file_path, strict, |f| {
+        if filter.is_some_and(|filter| !filter.matches(&f.properties)) {
+            filtered += 1;
+            return;
+        }
+        features.push(GridFeature::from_geom(
+            f.geometry,
+            f.properties,
+            simplification,
+            is_area,
+            rings,
+        ))
+    })?;
+    Ok(InputFeatures {
+        features,
+        skipped,
+        filtered,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use geo::Point;
+
+    struct PointReader;
+
+    impl GeoReader for PointReader {
+        fn names(&self) -> &[&str] {
+            &["pt"]
+        }
+
+        fn detect(&self, header: &[u8], _is_complete: bool) -> bool {
+            header.starts_with(b"PT")
+        }
+
+        fn read(&self, _file_path: &str) -> Result<FeatureIter<'_>> {
+            Ok(Box::new(std::iter::once(Ok(Feature::new(
+                Geometry::Point(Point::new(1., 2.)),
+                Properties::new(),
+            )))))
+        }
+    }
+
+    struct InvalidReader;
+
+    impl GeoReader for InvalidReader {
+        fn names(&self) -> &[&str] {
+            &["invalid"]
+        }
+
+        fn read(&self, _file_path: &str) -> Result<FeatureIter<'_>> {
+            Ok(Box::new(
+                [
+                    Err(anyhow::anyhow!("Invalid geometry")),
+                    PointReader.read("").unwrap().next().unwrap(),
+                    Err(anyhow::Error::new(SkipReason::NullGeometry)),
+                ]
+                .into_iter(),
+            ))
+        }
+    }
+
+    #[test]
+    fn test_detect() {
+        let readers = ReaderRegistry::default();
+        assert_eq!(
+            readers.detect("test.GEOJSON", None).unwrap().names()[0],
+            "geojson"
+        );
+        assert_eq!(
+            readers.detect("test.geojson", Some("csv")).unwrap().names()[0],
+            "csv"
+        );
+        assert_eq!(
+            readers.detect("test.ndjson", None).unwrap().names()[0],
+            "geojsonseq"
+        );
+        assert!(readers.detect("test.geojson", Some("pt")).is_err());
+        assert_eq!(
+            readers
+                .detect("./fixtures/input.fgb", Some("fgb"))
+                .unwrap()
+                .names()[0],
+            "fgb"
+        );
+    }
+
+    #[test]
+    fn test_detect_header() {
+        let readers = ReaderRegistry::default();
+        let detect = |header: &[u8]| readers.detect_header(header, true).map(|r| r.names()[0]);
+        assert_eq!(
+            detect(include_bytes!("../fixtures/input.geojson")),
+            Some("geojson")
+        );
+        assert_eq!(
+            detect(include_bytes!("../fixtures/input.geojsonl")),
+            Some("geojsonseq")
+        );
+        assert_eq!(
+            detect(include_bytes!("../fixtures/input.topojson")),
+            Some("topojson")
+        );
+        assert_eq!(detect(include_bytes!("../fixtures/input.csv")), Some("csv"));
+        assert_eq!(
+            detect(include_bytes!("../fixtures/input_geom.csv")),
+            Some("csv")
+        );
+        assert_eq!(
+            detect(include_bytes!("../fixtures/input_area.shp")),
+            Some("shp")
+        );
+        assert_eq!(detect(include_bytes!("../fixtures/input.wkt")), Some("wkt"));
+        assert_eq!(detect(include_bytes!("../fixtures/input.wkb")), Some("wkb"));
+        assert_eq!(
+            detect(include_bytes!("../fixtures/input.hexwkb")),
+            Some("hexwkb")
+        );
+        assert_eq!(detect(include_bytes!("../fixtures/input.kml")), Some("kml"));
+        assert_eq!(
+            detect(include_bytes!("../fixtures/input_placemarks.kml")),
+            Some("kml")
+        );
+        assert_eq!(detect(include_bytes!("../fixtures/input.gpx")), Some("gpx"));
+        assert_eq!(detect(include_bytes!("../fixtures/input.fgb")), Some("fgb"));
+        assert_eq!(
+            detect(include_bytes!("../fixtures/input.gpkg")),
+            Some("gpkg")
+        );
+        assert_eq!(detect(b"point_x,point_y\n1,2\n"), Some("csv"));
+        assert_eq!(detect(b"PK\x03\x04"), None);
+        // The last line is only used if the header holds the whole input
+        let ewkb = b"0101000020E6100000000000000000F03F0000000000000040";
+        assert_eq!(detect(ewkb), Some("hexwkb"));
+        assert!(readers.detect_header(ewkb, false).is_none());
+    }
+
+    #[test]
+    fn test_register() {
+        let mut readers = ReaderRegistry::default();
+        readers.register(PointReader);
+        let reader = readers.detect("test.pt", None).unwrap();
+        assert_eq!(
+            read_grid_features(reader, "test.pt", 0., false, Rings::All, true, None)
+                .unwrap()
+                .features[0]
+                .geoms,
+            vec![crate::map_grid::GridGeom::Point(Point::new(1., 2.))]
+        );
+        assert!(readers.readers[0].detect(b"PT 1 2", true));
+    }
+
+    #[test]
+    fn test_read_grid_features_skipped() {
+        let read =
+            read_grid_features(&InvalidReader, "test", 0., false, Rings::All, false, None).unwrap();
+        assert_eq!((read.read_count(), read.rendered_count()), (3, 1));
+        assert_eq!(read.skipped[0].index, 0);
+        assert_eq!(read.skipped[1].reason, SkipReason::NullGeometry);
+        assert_eq!(
+            read.summary("test"),
+            "test: read 3 features, rendered 1, skipped 2\n  parse error: 1 (features 0)\n    first error: Invalid geometry\n  null geometry: 1 (features 2)"
+        );
+        assert!(
+            read_grid_features(&InvalidReader, "test", 0., false, Rings::All, true, None).is_err()
+        );
+    }
+
+    #[test]
+    fn test_read_grid_features_filter() {
+        let filter = "id = 1".parse::<Filter>().unwrap();
+        let read = read_grid_features(
+            &PointReader,
+            "test",
+            0.,
+            false,
+            Rings::All,
+            false,
+            Some(&filter),
+        )
+        .unwrap();
+        assert_eq!((read.features.len(), read.filtered), (0, 1));
+        assert_eq!(
+            read.summary("test"),
+            "test: read 1 features, filtered out 1, rendered 0, skipped 0"
+        );
+    }
+}